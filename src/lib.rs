@@ -0,0 +1,15 @@
+#[macro_use] extern crate maplit;
+#[macro_use] extern crate failure;
+#[macro_use] extern crate failure_derive;
+#[macro_use] extern crate nom;
+extern crate regex;
+extern crate num_rational;
+extern crate num_traits;
+extern crate toml;
+
+pub mod parallel;
+pub mod token;
+pub mod parser;
+pub mod processor;
+pub mod types;
+pub mod recipe;
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use failure::Error;
+
+use super::gate::{Slot, SlotSet, Gate};
+use super::names::VariantNames;
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum AxisError {
+    #[fail(display = "axis names must start with a letter, and contain only letters, digits, '-' and '_'; found: {}", axis)]
+    Invalid {
+        axis: String,
+    },
+
+    #[fail(display = "axis declared more than once: {}", axis)]
+    Duplicate {
+        axis: String,
+    },
+
+    #[fail(display = "axis has no choices: {}", axis)]
+    NoChoices {
+        axis: String,
+    },
+
+    #[fail(display = "unknown axis: {}", axis)]
+    Unknown {
+        axis: String,
+    },
+
+    #[fail(display = "unknown choice for axis {}: {}", axis, choice)]
+    UnknownChoice {
+        axis: String,
+        choice: String,
+    },
+
+    #[fail(display = "no choice given for axis: {}", axis)]
+    Unassigned {
+        axis: String,
+    },
+
+    #[fail(display = "splits may not gate some branches by slot and others by axis")]
+    Mixed,
+}
+
+/// A choice of slot for each axis, by axis name.
+pub type Assignment = BTreeMap<String, Slot>;
+
+/* Condition */
+
+/// Decides whether a branch of a split is taken, based on the choices made for one or more axes.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Condition {
+    /// Holds if the choice for the named axis is allowed by the gate.
+    On(String, Gate),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+impl Condition {
+    pub fn on<S: Into<String>>(axis: S, gate: Gate) -> Self {
+        Condition::On(axis.into(), gate)
+    }
+
+    pub fn negate(condition: Condition) -> Self {
+        Condition::Not(Box::new(condition))
+    }
+
+    /// Combines conditions so that all of them must hold, without nesting a lone condition.
+    pub fn all<II: IntoIterator<Item = Condition>>(conditions: II) -> Self {
+        let mut conditions: Vec<_> = conditions.into_iter().collect();
+
+        match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::All(conditions),
+        }
+    }
+
+    /// Combines conditions so that any of them must hold, without nesting a lone condition.
+    pub fn any<II: IntoIterator<Item = Condition>>(conditions: II) -> Self {
+        let mut conditions: Vec<_> = conditions.into_iter().collect();
+
+        match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::Any(conditions),
+        }
+    }
+
+    /// Whether this condition holds for an assignment, which must have a choice for every axis mentioned.
+    pub fn holds(&self, assignment: &Assignment) -> Result<bool, AxisError> {
+        match *self {
+            Condition::On(ref axis, ref gate) => match assignment.get(axis) {
+                Some(&slot) => Ok(gate.allows_slot(slot)),
+                None => Err(AxisError::Unassigned{axis: axis.clone()}),
+            },
+            Condition::Not(ref condition) => condition.holds(assignment).map(|b| !b),
+            Condition::All(ref conditions) => {
+                for condition in conditions {
+                    if !condition.holds(assignment)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            Condition::Any(ref conditions) => {
+                for condition in conditions {
+                    if condition.holds(assignment)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            },
+        }
+    }
+
+    /// Adds the slots mentioned by this condition to those already collected for each axis.
+    pub fn collect_slots(&self, mentioned: &mut BTreeMap<String, SlotSet>) {
+        match *self {
+            Condition::On(ref axis, ref gate) => mentioned.entry(axis.clone()).or_default().extend(gate.slots()),
+            Condition::Not(ref condition) => condition.collect_slots(mentioned),
+            Condition::All(ref conditions) | Condition::Any(ref conditions) => {
+                for condition in conditions {
+                    condition.collect_slots(mentioned);
+                }
+            },
+        }
+    }
+}
+
+/* Axes */
+
+/// Independent dimensions along which a recipe varies, e.g. diet or cooking method, each with its own named choices.
+/// Unlike the slots of a slot stack, the choice for an axis does not depend on how deeply a split is nested.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Axes(Vec<(String, VariantNames)>);
+
+impl Axes {
+    pub fn new() -> Self {
+        Axes::default()
+    }
+
+    /// Declares a new axis along with its choices, which are given slots in the order they are declared.
+    pub fn declare<S: Into<String>>(&mut self, axis: S, choices: VariantNames) -> Result<(), Error> {
+        let axis = axis.into();
+
+        if !VariantNames::is_valid_name(&axis) {
+            Err(AxisError::Invalid{axis: axis.clone()})?;
+        }
+        else if self.choices(&axis).is_some() {
+            Err(AxisError::Duplicate{axis: axis.clone()})?;
+        }
+        else if choices.is_empty() {
+            Err(AxisError::NoChoices{axis: axis.clone()})?;
+        }
+
+        self.0.push((axis, choices));
+        Ok(())
+    }
+
+    pub fn choices(&self, axis: &str) -> Option<&VariantNames> {
+        self.0.iter().find(|&(a, _)| a == axis).map(|(_, choices)| choices)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &VariantNames)> {
+        self.0.iter().map(|(axis, choices)| (axis.as_str(), choices))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Builds a full assignment from the name of a choice for each declared axis.
+    pub fn assignment(&self, choices: &[(&str, &str)]) -> Result<Assignment, Error> {
+        let mut assignment = Assignment::new();
+
+        for &(axis, choice) in choices {
+            let slot = self.choices(axis)
+                .ok_or_else(|| AxisError::Unknown{axis: axis.to_string()})?
+                .slot(choice)
+                .ok_or_else(|| AxisError::UnknownChoice{axis: axis.to_string(), choice: choice.to_string()})?;
+
+            assignment.insert(axis.to_string(), slot);
+        }
+
+        if let Some((axis, _)) = self.iter().find(|&(axis, _)| !assignment.contains_key(axis)) {
+            Err(AxisError::Unassigned{axis: axis.to_string()})?;
+        }
+
+        Ok(assignment)
+    }
+
+    /// Produces every assignment of a declared choice to each axis, varying the last declared axis fastest.
+    pub fn assignments(&self) -> Vec<Assignment> {
+        self.0.iter().fold(vec![Assignment::new()], |assignments, (axis, choices)| {
+            assignments.iter()
+                .flat_map(|assignment| (0..choices.len()).map(move |slot| {
+                    let mut assignment = assignment.clone();
+                    assignment.insert(axis.clone(), slot as Slot);
+                    assignment
+                }))
+                .collect()
+        })
+    }
+
+    /// Describes an assignment by name, in the order the axes were declared, e.g. "diet: vegan".
+    /// Axes that were not declared follow in order of name, and choices without a name are given by number.
+    pub fn label(&self, assignment: &Assignment) -> Vec<String> {
+        let declared = self.0.iter()
+            .filter_map(|(axis, choices)| assignment.get(axis).map(|&slot| (axis, Some(choices), slot)));
+        let undeclared = assignment.iter()
+            .filter(|&(axis, _)| self.choices(axis).is_none())
+            .map(|(axis, &slot)| (axis, None, slot));
+
+        declared.chain(undeclared)
+            .map(|(axis, choices, slot)| {
+                let choice = choices.and_then(|c| c.name(slot)).map_or_else(|| slot.to_string(), str::to_string);
+                format!("{}: {}", axis, choice)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Assignment, AxisError, Axes, Condition};
+
+    use parallel::gate::Gate;
+    use parallel::names::VariantNames;
+
+    fn names(names: &[&str]) -> VariantNames {
+        let mut variants = VariantNames::new();
+        for name in names {
+            variants.declare(*name).expect("Unable to declare");
+        }
+        variants
+    }
+
+    fn assign(choices: &[(&str, u8)]) -> Assignment {
+        choices.iter().map(|&(axis, slot)| (axis.to_string(), slot)).collect()
+    }
+
+    #[test]
+    fn test_holds() {
+        let vegan = Condition::on("diet", allow![0]);
+        let grilled = Condition::on("method", allow![1]);
+
+        let inputs_and_expected = vec![
+            (vegan.clone(), assign(&[("diet", 0)]), Ok(true)),
+            (vegan.clone(), assign(&[("diet", 1)]), Ok(false)),
+            (Condition::negate(vegan.clone()), assign(&[("diet", 1)]), Ok(true)),
+            (Condition::all(vec![vegan.clone(), grilled.clone()]), assign(&[("diet", 0), ("method", 1)]), Ok(true)),
+            (Condition::all(vec![vegan.clone(), grilled.clone()]), assign(&[("diet", 0), ("method", 0)]), Ok(false)),
+            (Condition::any(vec![vegan.clone(), grilled.clone()]), assign(&[("diet", 2), ("method", 1)]), Ok(true)),
+            (Condition::any(vec![]), assign(&[]), Ok(false)),
+            (Condition::all(vec![]), assign(&[]), Ok(true)),
+            (grilled.clone(), assign(&[("diet", 0)]), Err(AxisError::Unassigned{axis: "method".to_string()})),
+        ];
+
+        for (condition, assignment, expected) in inputs_and_expected {
+            let produced = condition.holds(&assignment);
+            assert_eq!(expected, produced);
+        }
+
+        assert_eq!(vegan, Condition::any(vec![vegan.clone()]));
+    }
+
+    #[test]
+    fn test_declare() {
+        let inputs_and_expected = vec![
+            (vec!["diet", "method"], Ok(())),
+            (vec!["diet", "diet"], Err(AxisError::Duplicate{axis: "diet".to_string()})),
+            (vec!["2nd"], Err(AxisError::Invalid{axis: "2nd".to_string()})),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut axes = Axes::new();
+            let produced = input.into_iter()
+                .map(|axis| axes.declare(axis, names(&["a"])))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|_| ())
+                .map_err(|e| e.downcast::<AxisError>().expect("Unexpected error type"));
+            assert_eq!(expected, produced);
+        }
+
+        let produced = Axes::new().declare("diet", VariantNames::new()).unwrap_err().downcast::<AxisError>().expect("Unexpected error type");
+        assert_eq!(AxisError::NoChoices{axis: "diet".to_string()}, produced);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let mut axes = Axes::new();
+        axes.declare("diet", names(&["vegan", "omnivore"])).expect("Unable to declare");
+        axes.declare("method", names(&["grill", "oven", "pan"])).expect("Unable to declare");
+
+        let produced: Vec<_> = axes.assignments().iter().map(|a| axes.label(a)).collect();
+        assert_eq!(6, produced.len());
+        assert_eq!(vec!["diet: vegan", "method: grill"], produced[0]);
+        assert_eq!(vec!["diet: vegan", "method: oven"], produced[1]);
+        assert_eq!(vec!["diet: omnivore", "method: pan"], produced[5]);
+
+        assert_eq!(vec!["diet: 4", "heat: 1"], axes.label(&assign(&[("heat", 1), ("diet", 4)])));
+        assert_eq!(vec![Assignment::new()], Axes::new().assignments());
+
+        assert_eq!(assign(&[("diet", 1), ("method", 2)]), axes.assignment(&[("method", "pan"), ("diet", "omnivore")]).expect("Unable to assign"));
+
+        let errors = vec![
+            (vec![("diet", "vegan")], AxisError::Unassigned{axis: "method".to_string()}),
+            (vec![("diet", "keto"), ("method", "pan")], AxisError::UnknownChoice{axis: "diet".to_string(), choice: "keto".to_string()}),
+            (vec![("heat", "mild")], AxisError::Unknown{axis: "heat".to_string()}),
+        ];
+
+        for (input, expected) in errors {
+            let produced = axes.assignment(&input).unwrap_err().downcast::<AxisError>().expect("Unexpected error type");
+            assert_eq!(expected, produced);
+        }
+    }
+}
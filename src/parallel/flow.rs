@@ -1,14 +1,16 @@
 #![macro_use]
 
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::iter::IntoIterator;
 use std::borrow::Cow;
 
 use failure::Error;
 
-use super::gate::{Slot, Gate};
+use super::axis::{Assignment, AxisError, Condition};
+use super::gate::{Slot, SlotSet, Gate};
 use token::Token;
 
+#[cfg_attr(not(test), allow(unused_macros))]
 macro_rules! splitset {
     ( $($split:expr),* $(,)? ) => (SplitSet::new(btreeset!($($split),*)));
 }
@@ -28,15 +30,26 @@ pub enum SlotStackError {
     },
 }
 
-/** FlowItem **/
+/* Comment */
+
+/// A comment in recipe source, kept as trivia so that formatting a recipe does not lose it.
+/// Comments have no effect on the walks through a flow.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Comment {
+    Line(String),
+    Block(String),
+}
+
+/* FlowItem */
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub enum FlowItem<'a> {
     Token(Token),
     Split(SplitSet<'a>),
+    Comment(Comment),
 }
 
-/** Flow **/
+/* Flow */
 
 /// Contains the tokens and splits that comprise all the variants of a single recipe.
 #[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
@@ -86,20 +99,29 @@ impl<'a> Flow<'a> {
         Flow(flow)
     }
 
-    fn find_walks(&self, mut slot_stack: &mut Vec<Slot>) -> Result<Vec<Vec<&Token>>, Error> {
+    pub fn items(&self) -> &[FlowItem<'a>] {
+        &self.0
+    }
+
+    fn find_walks(&self, slot_stack: &mut Vec<Slot>) -> Result<Vec<Vec<&Token>>, Error> {
         let mut results: Vec<Vec<&Token>> = vec![vec![]];
         let mut opt_target_slot: Option<Slot> = None;
 
         // Iterate through all items in this flow.
         for flow_item in &self.0 {
-            match flow_item {
-                &FlowItem::Token(ref token) => {
+            match *flow_item {
+                FlowItem::Token(ref token) => {
                     // Append this token to each result.
-                    for mut result in &mut results {
+                    for result in &mut results {
                         result.push(token);
                     }
                 },
-                &FlowItem::Split(ref split_set) => {
+                FlowItem::Split(ref split_set) => {
+                    // Without an assignment, there is no way to choose between branches that depend on axes.
+                    if let Some(axis) = split_set.axes().into_keys().next() {
+                        Err(AxisError::Unassigned{axis})?;
+                    }
+
                     // NOTE: This code is in charge of popping off the slots from the slot stack.
                     // Since we are about to start a split, set the target slot if not already set,
                     // and use the value contained.
@@ -109,7 +131,7 @@ impl<'a> Flow<'a> {
 
                     let target_slot = opt_target_slot.ok_or(SlotStackError::Empty)?;
 
-                    let mut split_set_walks = split_set.find_walks(target_slot, &mut slot_stack)?;
+                    let split_set_walks = split_set.find_walks(target_slot, slot_stack)?;
 
                     // For each existing result walk, append each of the split set walks.
                     let mut new_results: Vec<Vec<&Token>> = vec![];
@@ -124,12 +146,166 @@ impl<'a> Flow<'a> {
 
                     results = new_results;
                 },
+                FlowItem::Comment(_) => {},
             }
         }
 
         Ok(results)
     }
 
+    /// The number of levels of split nesting in this flow, and so the length of a full slot stack.
+    /// Splits whose branches depend on axes do not count as a level of nesting.
+    pub fn depth(&self) -> usize {
+        self.0.iter()
+            .filter_map(|flow_item| match *flow_item {
+                FlowItem::Split(ref split_set) => Some(split_set.0.iter().map(|s| s.flow.depth()).max().unwrap_or(0) + split_set.levels()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Lazily produces the walks through this flow, one at a time, for a slot stack holding one slot per level of split nesting.
+    /// Unlike `walks`, no prefix is copied for each of the walks that share it, and iteration may be stopped early.
+    pub fn iter_walks(&self, slot_stack: Vec<Slot>) -> Result<Walks<'_, 'a>, Error> {
+        self.iter_walks_with(slot_stack, &Assignment::new())
+    }
+
+    /// Like `iter_walks`, but also choosing between branches that depend on axes, using an assignment that must
+    /// have a choice for every axis mentioned in this flow.
+    pub fn iter_walks_with(&self, slot_stack: Vec<Slot>, assignment: &Assignment) -> Result<Walks<'_, 'a>, Error> {
+        self.check_slot_stack(&slot_stack)?;
+        self.check_assignment(assignment)?;
+
+        Ok(Walks::new(self, Selection::of(&slot_stack, assignment)))
+    }
+
+    /// Counts the walks through this flow for a slot stack holding one slot per level of split nesting, without producing them.
+    pub fn variant_count(&self, slot_stack: &[Slot]) -> Result<u64, Error> {
+        self.variant_count_with(slot_stack, &Assignment::new())
+    }
+
+    /// Like `variant_count`, but also choosing between branches that depend on axes, using an assignment.
+    pub fn variant_count_with(&self, slot_stack: &[Slot], assignment: &Assignment) -> Result<u64, Error> {
+        self.check_slot_stack(slot_stack)?;
+        self.check_assignment(assignment)?;
+
+        Ok(self.count_walks(Selection::of(slot_stack, assignment)))
+    }
+
+    /// Counts the distinct walks through this flow across every slot stack and assignment, without producing them.
+    /// Walks are told apart by the branches they take, so two walks that take different branches count separately,
+    /// even if their tokens happen to be the same.
+    pub fn total_variant_count(&self) -> u64 {
+        self.count_walks(Selection { levels: vec![Gate::allow_all(); self.depth()], assignments: self.assignments() })
+    }
+
+    /// Lazily produces every walk through this flow, along with a slot stack and assignment that select it.
+    /// Rather than trying every slot in turn, the slots able to select a walk are narrowed by each gate it passes through,
+    /// so slots are only told apart where a gate mentions them, and one representative stands in for all the others.
+    pub fn variants(&self) -> Variants<'_, 'a> {
+        self.variants_with(self.assignments()).expect("generated assignments have a choice for every axis")
+    }
+
+    /// Like `variants`, but trying only the given assignments, each of which must have a choice for every axis in this flow.
+    pub fn variants_with(&self, assignments: Vec<Assignment>) -> Result<Variants<'_, 'a>, Error> {
+        for assignment in &assignments {
+            self.check_assignment(assignment)?;
+        }
+
+        Ok(Variants(Walks::new(self, Selection { levels: vec![Gate::allow_all(); self.depth()], assignments })))
+    }
+
+    /// The slots mentioned by the conditions in this flow, for each axis they mention.
+    pub fn axes(&self) -> BTreeMap<String, SlotSet> {
+        let mut mentioned = BTreeMap::new();
+        self.collect_axes(&mut mentioned);
+        mentioned
+    }
+
+    /// Produces one assignment per distinct way of choosing between the branches that depend on axes in this flow.
+    /// Choices are only told apart by the conditions that mention them, so it suffices to try every choice mentioned
+    /// for an axis, plus one representative of all the others.
+    pub fn assignments(&self) -> Vec<Assignment> {
+        self.axes().into_iter().fold(vec![Assignment::new()], |assignments, (axis, slots)| {
+            let other = (Slot::MIN..=Slot::MAX).find(|s| !slots.contains(s));
+            let candidates: Vec<Slot> = slots.into_iter().chain(other).collect();
+            let axis = &axis;
+
+            assignments.iter()
+                .flat_map(|assignment| candidates.iter().map(move |&slot| {
+                    let mut assignment = assignment.clone();
+                    assignment.insert(axis.clone(), slot);
+                    assignment
+                }))
+                .collect()
+        })
+    }
+
+    fn collect_axes(&self, mentioned: &mut BTreeMap<String, SlotSet>) {
+        for flow_item in &self.0 {
+            if let FlowItem::Split(ref split_set) = *flow_item {
+                for split in &split_set.0 {
+                    if let Some(ref condition) = split.condition {
+                        condition.collect_slots(mentioned);
+                    }
+
+                    split.flow.collect_axes(mentioned);
+                }
+            }
+        }
+    }
+
+    // Counts the walks that can be selected by the slots and assignments in a selection.
+    fn count_walks(&self, selection: Selection) -> u64 {
+        self.count_paths(0, btreemap![selection => 1]).values().fold(0, |total, &count| total.saturating_add(count))
+    }
+
+    // Follows the branches through this flow that can be taken, narrowing the selection by each branch taken.
+    // Paths that leave the same selection can only continue in the same ways, so they are counted together rather than
+    // being followed one by one.
+    fn count_paths(&self, depth: usize, paths: BTreeMap<Selection, u64>) -> BTreeMap<Selection, u64> {
+        self.0.iter().fold(paths, |paths, flow_item| match *flow_item {
+            FlowItem::Split(ref split_set) => {
+                let mut new_paths = BTreeMap::new();
+
+                for (selection, count) in paths {
+                    for split in &split_set.0 {
+                        let branch = match selection.narrow(split_set, split, depth) {
+                            Some(branch) => branch,
+                            None => continue,
+                        };
+
+                        for (branch, branch_count) in split.flow.count_paths(depth + split_set.levels(), btreemap![branch => count]) {
+                            let total = new_paths.entry(branch).or_insert(0u64);
+                            *total = total.saturating_add(branch_count);
+                        }
+                    }
+                }
+
+                new_paths
+            },
+            _ => paths,
+        })
+    }
+
+    fn check_slot_stack(&self, slot_stack: &[Slot]) -> Result<(), Error> {
+        let depth = self.depth();
+
+        ensure!(slot_stack.len() >= depth, SlotStackError::Empty);
+        ensure!(slot_stack.len() <= depth, SlotStackError::Leftover{leftover: slot_stack[..slot_stack.len() - depth].to_vec()});
+
+        Ok(())
+    }
+
+    fn check_assignment(&self, assignment: &Assignment) -> Result<(), Error> {
+        if let Some(axis) = self.axes().into_keys().find(|axis| !assignment.contains_key(axis)) {
+            Err(AxisError::Unassigned{axis})?;
+        }
+
+        Ok(())
+    }
+
     pub fn walks(&self, slot_stack: Vec<Slot>) -> Result<Vec<Vec<&Token>>, Error> {
         let mut slot_stack = slot_stack.clone();
 
@@ -141,12 +317,218 @@ impl<'a> Flow<'a> {
     }
 }
 
-/** Split **/
+/* Selection */
+
+// The slots at each level of nesting, outermost first, and the assignments that are still able to select a walk.
+#[derive(Clone, PartialEq, Eq, Debug, PartialOrd, Ord)]
+struct Selection {
+    levels: Vec<Gate>,
+    assignments: Vec<Assignment>,
+}
+
+impl Selection {
+    // Selects only the walks for a slot stack, with the outermost level on top, and an assignment.
+    fn of(slot_stack: &[Slot], assignment: &Assignment) -> Self {
+        Selection {
+            levels: slot_stack.iter().rev().map(|&slot| Gate::allow(vec![slot])).collect(),
+            assignments: vec![assignment.clone()],
+        }
+    }
+
+    // Narrows this selection to take a split from a split set at a level of nesting, provided that it still can.
+    fn narrow(&self, split_set: &SplitSet, split: &Split, depth: usize) -> Option<Self> {
+        let mut selection = self.clone();
+
+        match split_set.is_conditional() {
+            true => {
+                selection.assignments.retain(|assignment| split.condition.as_ref().is_none_or(|c| {
+                    c.holds(assignment).expect("assignments are checked before walking")
+                }));
+
+                Some(selection).filter(|s| !s.assignments.is_empty())
+            },
+            false => {
+                selection.levels[depth] = selection.levels[depth].intersection(&split.gate);
+
+                Selection::representative(&selection.levels[depth]).map(|_| selection)
+            },
+        }
+    }
+
+    // A slot stack that selects the same walks as this selection, with the outermost level of nesting on top.
+    fn slot_stack(&self) -> Vec<Slot> {
+        self.levels.iter()
+            .rev()
+            .map(|gate| Selection::representative(gate).expect("selections only pass through gates that allow some slot"))
+            .collect()
+    }
+
+    // An assignment that selects the same walks as this selection.
+    fn assignment(&self) -> Assignment {
+        self.assignments.first().cloned().unwrap_or_default()
+    }
+
+    // The first slot that a gate allows, to stand in for every slot it allows.
+    fn representative(gate: &Gate) -> Option<Slot> {
+        (Slot::MIN..=Slot::MAX).find(|&slot| gate.allows_slot(slot))
+    }
+}
+
+/* Variant */
+
+/// A single walk through a flow, along with a slot stack and assignment that select it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variant<'f> {
+    pub slot_stack: Vec<Slot>,
+    pub assignment: Assignment,
+    pub walk: Vec<&'f Token>,
+}
+
+/// An iterator over the variants of a flow, in the same order as the walks that make them.
+pub struct Variants<'f, 'a: 'f>(Walks<'f, 'a>);
+
+impl<'f, 'a: 'f> Iterator for Variants<'f, 'a> {
+    type Item = Variant<'f>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let walk = self.0.next()?;
+
+        Some(Variant { slot_stack: self.0.selection.slot_stack(), assignment: self.0.selection.assignment(), walk })
+    }
+}
+
+/* Walks */
+
+// A position within a flow that is currently being walked.
+#[derive(Clone)]
+struct Cursor<'f, 'a: 'f> {
+    flow: &'f Flow<'a>,
+    pos: usize,
+    depth: usize,
+}
+
+// A split whose remaining branches have yet to be walked, along with what to go back to when walking them.
+struct Choice<'f, 'a: 'f> {
+    path_len: usize,
+    cursors: Vec<Cursor<'f, 'a>>,
+    branches: Vec<(&'f Flow<'a>, Selection)>,
+    depth: usize,
+}
+
+/// A depth-first iterator over the walks through a flow, which backtracks to the most recent split with branches left.
+pub struct Walks<'f, 'a: 'f> {
+    selection: Selection,
+    path: Vec<&'f Token>,
+    cursors: Vec<Cursor<'f, 'a>>,
+    choices: Vec<Choice<'f, 'a>>,
+    started: bool,
+}
+
+impl<'f, 'a: 'f> Walks<'f, 'a> {
+    fn new(flow: &'f Flow<'a>, selection: Selection) -> Self {
+        Walks {
+            selection,
+            path: vec![],
+            cursors: vec![Cursor { flow, pos: 0, depth: 0 }],
+            choices: vec![],
+            started: false,
+        }
+    }
+
+    // Continues walking from the current position, until either a walk is finished or a dead end is reached.
+    fn advance(&mut self) -> bool {
+        loop {
+            let (flow_item, depth) = match self.cursors.last_mut() {
+                None => return true,
+                Some(cursor) => match cursor.flow.0.get(cursor.pos) {
+                    Some(flow_item) => {
+                        cursor.pos += 1;
+                        (flow_item, cursor.depth)
+                    },
+                    None => {
+                        self.cursors.pop();
+                        continue;
+                    },
+                },
+            };
+
+            match *flow_item {
+                FlowItem::Token(ref token) => self.path.push(token),
+                FlowItem::Split(ref split_set) => {
+                    let mut branches: Vec<_> = split_set.0.iter()
+                        .filter_map(|split| self.selection.narrow(split_set, split, depth).map(|s| (split.flow.as_ref(), s)))
+                        .rev()
+                        .collect();
+                    let depth = depth + split_set.levels();
+
+                    match branches.pop() {
+                        Some((branch, selection)) => {
+                            if !branches.is_empty() {
+                                self.choices.push(Choice { path_len: self.path.len(), cursors: self.cursors.clone(), branches, depth });
+                            }
+
+                            self.selection = selection;
+                            self.cursors.push(Cursor { flow: branch, pos: 0, depth });
+                        },
+                        None => return false,
+                    }
+                },
+                FlowItem::Comment(_) => {},
+            }
+        }
+    }
+
+    // Goes back to the most recent split with branches left, and starts walking the next of them.
+    fn backtrack(&mut self) -> bool {
+        let mut choice = match self.choices.pop() {
+            Some(choice) => choice,
+            None => return false,
+        };
+
+        let (branch, selection) = choice.branches.pop().expect("choices always have a branch left");
+
+        self.path.truncate(choice.path_len);
+        self.cursors = choice.cursors.clone();
+        self.cursors.push(Cursor { flow: branch, pos: 0, depth: choice.depth });
+        self.selection = selection;
+
+        if !choice.branches.is_empty() {
+            self.choices.push(choice);
+        }
+
+        true
+    }
+}
+
+impl<'f, 'a: 'f> Iterator for Walks<'f, 'a> {
+    type Item = Vec<&'f Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.started && !self.backtrack() {
+            return None;
+        }
+
+        self.started = true;
+
+        loop {
+            if self.advance() {
+                return Some(self.path.clone());
+            }
+
+            if !self.backtrack() {
+                return None;
+            }
+        }
+    }
+}
+
+/* Split */
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub struct Split<'a> {
     flow: Cow<'a, Flow<'a>>,
     gate: Cow<'a, Gate>,
+    condition: Option<Condition>,
 }
 
 impl<'a> Split<'a> {
@@ -154,7 +536,26 @@ impl<'a> Split<'a> {
     where F: Into<Cow<'a, Flow<'a>>>,
           G: Into<Cow<'a, Gate>>,
     {
-        Split { flow: flow.into(), gate: gate.into() }
+        Split { flow: flow.into(), gate: gate.into(), condition: None }
+    }
+
+    /// Creates a split that is taken based on the choices made for one or more axes, rather than on a slot.
+    pub fn conditional<F>(flow: F, condition: Condition) -> Self
+    where F: Into<Cow<'a, Flow<'a>>>,
+    {
+        Split { flow: flow.into(), gate: Cow::Owned(Gate::allow_all()), condition: Some(condition) }
+    }
+
+    pub fn flow(&self) -> &Flow<'a> {
+        &self.flow
+    }
+
+    pub fn gate(&self) -> &Gate {
+        &self.gate
+    }
+
+    pub fn condition(&self) -> Option<&Condition> {
+        self.condition.as_ref()
     }
 
     fn find_walks(&self, target_slot: Slot, slot_stack: &mut Vec<Slot>) -> Result<Vec<Vec<&Token>>, Error> {
@@ -168,7 +569,7 @@ impl<'a> Split<'a> {
     }
 }
 
-/** SplitSet **/
+/* SplitSet */
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
 pub struct SplitSet<'a>(BTreeSet<Split<'a>>);
@@ -180,6 +581,33 @@ impl<'a> SplitSet<'a> {
         SplitSet(SplitSet::normalize_splits(splits))
     }
 
+    pub fn splits(&self) -> &BTreeSet<Split<'a>> {
+        &self.0
+    }
+
+    /// Whether the branches of this split set are chosen by conditions on axes, rather than by a slot.
+    /// Such a split set does not take up a level of the slot stack, and the gates of its splits are not used.
+    pub fn is_conditional(&self) -> bool {
+        self.0.iter().any(|split| split.condition.is_some())
+    }
+
+    /// The slots mentioned by the conditions in this split set, not including any nested within its branches.
+    pub fn axes(&self) -> BTreeMap<String, SlotSet> {
+        let mut mentioned = BTreeMap::new();
+        for condition in self.0.iter().filter_map(Split::condition) {
+            condition.collect_slots(&mut mentioned);
+        }
+        mentioned
+    }
+
+    // The number of levels of the slot stack that this split set takes up.
+    fn levels(&self) -> usize {
+        match self.is_conditional() {
+            true => 0,
+            false => 1,
+        }
+    }
+
     // pub fn normalize_splits<'b, II>(splits: II) -> BTreeSet<Split<'b>>
     // where II: IntoIterator<Item = Split<'b>>
     // {
@@ -221,7 +649,7 @@ impl<'a> SplitSet<'a> {
 
     //         flow_to_gate
     //             .entry(flow)
-    //             .and_modify(|present| { *present = Cow::Owned(gate.union(&present)) })
+    //             .and_modify(|present| { *present = Cow::Owned(gate.union(present)) })
     //             .or_insert(gate);
     //     }
 
@@ -231,14 +659,15 @@ impl<'a> SplitSet<'a> {
     pub fn normalize_splits<'b, II>(splits: II) -> BTreeSet<Split<'b>>
     where II: IntoIterator<Item = Split<'b>>
     {
-        let mut flow_to_gate: HashMap<Cow<Flow>, Cow<Gate>> = hashmap![];
+        let mut flow_to_gate: HashMap<Cow<Flow>, (Cow<Gate>, Option<Condition>)> = hashmap![];
 
         // Iterate over all splits that do not have a block-all gate.
         for split in splits.into_iter().filter(|s| !s.gate.is_block_all()) {
-            // Break apart split into flow and gate.
+            // Break apart split into flow, gate and condition.
             // LEARN: These cause moves, and the split is no longer usable.
             let flow = split.flow;
             let gate = split.gate;
+            let condition = split.condition;
 
             // TODO: If doing recursion, logic should live here.
             // Would need to have a method on Flow, which returns a new Flow with normalized Split enums.
@@ -246,30 +675,59 @@ impl<'a> SplitSet<'a> {
             // Store in mapping.
             flow_to_gate
                 .entry(flow)
-                .and_modify(|present| { *present = Cow::Owned(gate.union(&present)) })
-                .or_insert(gate);
+                .and_modify(|present| {
+                    present.0 = Cow::Owned(gate.union(&present.0));
+                    present.1 = SplitSet::either(present.1.take(), condition.clone());
+                })
+                .or_insert((gate, condition));
         }
 
         // Calculate the union gate.
-        let union_gate = flow_to_gate.values().fold(Gate::block_all(), |acc_g, ref g| acc_g.union(&g));
+        let union_gate = flow_to_gate.values().fold(Gate::block_all(), |acc_g, (g, _)| acc_g.union(g));
 
         // Store/modify empty flow in mapping if the union gate is not allow-all.
         if !union_gate.is_allow_all() {
             let inv_union_gate = union_gate.invert();
             flow_to_gate
                 .entry(Cow::Owned(flow![]))
-                .and_modify(|present| { *present = Cow::Owned(inv_union_gate.union(&present)) })
-                .or_insert(Cow::Owned(inv_union_gate));
+                .and_modify(|present| { present.0 = Cow::Owned(inv_union_gate.union(&present.0)) })
+                .or_insert((Cow::Owned(inv_union_gate), None));
+        }
+
+        // Likewise, if every split has a condition, store/modify empty flow in mapping to be taken when none of them hold.
+        let conditions: Option<Vec<Condition>> = flow_to_gate.values().map(|(_, c)| c.clone()).collect();
+
+        if let Some(mut conditions) = conditions.filter(|c| !c.is_empty()) {
+            conditions.sort();
+            let escape = Condition::negate(Condition::any(conditions));
+            flow_to_gate
+                .entry(Cow::Owned(flow![]))
+                .and_modify(|present| { present.1 = SplitSet::either(present.1.take(), Some(escape.clone())) })
+                .or_insert((Cow::Owned(Gate::allow_all()), Some(escape)));
         }
 
-        flow_to_gate.into_iter().map(|(f, g)| Split::new(f, g)).collect::<BTreeSet<Split>>()
+        flow_to_gate.into_iter()
+            .map(|(flow, (gate, condition))| Split { flow, gate, condition })
+            .collect::<BTreeSet<Split>>()
+    }
+
+    // Combines the conditions of two splits with identical flows, where a missing condition always holds.
+    fn either(a: Option<Condition>, b: Option<Condition>) -> Option<Condition> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let mut conditions = vec![a, b];
+                conditions.sort();
+                Some(Condition::any(conditions))
+            },
+            _ => None,
+        }
     }
 
     /// Produces all walks through the contained splits that allow a given slot.
-    fn find_walks(&self, target_slot: Slot, slot_stack: &mut Vec<Slot>) -> Result<Vec<Vec<&Token>>, Error> {
+    fn find_walks(&self, target_slot: Slot, slot_stack: &[Slot]) -> Result<Vec<Vec<&Token>>, Error> {
         let mut results: Vec<Vec<&Token>> = vec![];
         for split in &self.0 {
-            let mut split_result = split.find_walks(target_slot, &mut slot_stack.clone())?;
+            let mut split_result = split.find_walks(target_slot, &mut slot_stack.to_vec())?;
             results.append(&mut split_result);
         }
 
@@ -279,8 +737,9 @@ impl<'a> SplitSet<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Flow, FlowItem, Split, SplitSet};
+    use super::{Comment, Flow, FlowItem, Split, SplitSet};
 
+    use super::super::axis::{Assignment, Condition};
     use super::super::gate::{Gate, Slot};
     use token::Token;
 
@@ -292,7 +751,7 @@ mod tests {
         let token_d = Token::Ingredient("date".to_string());
 
         let inputs_and_expected = vec![
-            ((flow![FlowItem::Token(token_a.clone())], vec![0: Slot]),
+            ((flow![FlowItem::Token(token_a.clone())], vec![0 as Slot]),
                 vec![vec![&token_a]]),
             ((flow![FlowItem::Token(token_a.clone()), FlowItem::Token(token_b.clone())], vec![0]),
                 vec![vec![&token_a, &token_b]]),
@@ -410,6 +869,24 @@ mod tests {
                 ),
                 vec![vec![&token_a, &token_c]],
             ),
+            (
+                (
+                    flow![
+                        FlowItem::Comment(Comment::Line("fruit first".to_string())),
+                        FlowItem::Token(token_a.clone()),
+                        FlowItem::Split(
+                            splitset!(
+                                Split::new(
+                                    flow!(FlowItem::Comment(Comment::Block("optional".to_string())), FlowItem::Token(token_b.clone())),
+                                    allow!(0),
+                                ),
+                            ),
+                        ),
+                    ],
+                    vec![0]
+                ),
+                vec![vec![&token_a, &token_b]],
+            ),
         ];
 
         for ((flow, slot_stack), expected) in inputs_and_expected {
@@ -418,6 +895,213 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_iter_walks() {
+        let token = |s: &str| FlowItem::Token(Token::Ingredient(s.to_string()));
+
+        let nested = flow![
+            token("apple"),
+            FlowItem::Split(splitset![
+                Split::new(flow![token("banana"), FlowItem::Split(splitset![
+                    Split::new(flow![token("cherry")], allow![0]),
+                    Split::new(flow![token("date")], allow![0, 1]),
+                ])], allow![0, 2]),
+                Split::new(flow![token("elderberry")], allow![1, 2]),
+            ]),
+            FlowItem::Comment(Comment::Line("then".to_string())),
+            FlowItem::Split(splitset![
+                Split::new(flow![token("fig")], allow![2]),
+            ]),
+            token("grape"),
+        ];
+
+        let inputs_and_expected = vec![
+            (flow![], vec![]),
+            (flow![token("apple")], vec![]),
+            (nested.clone(), vec![0 as Slot, 0]),
+            (nested.clone(), vec![1, 0]),
+            (nested.clone(), vec![0, 2]),
+            (nested.clone(), vec![1, 2]),
+            (nested.clone(), vec![3, 3]),
+        ];
+
+        for (flow, slot_stack) in inputs_and_expected {
+            let expected = flow.find_walks(&mut slot_stack.clone()).expect("Unable to find walks");
+            let produced: Vec<_> = flow.iter_walks(slot_stack).expect("Unable to iterate walks").collect();
+            assert_eq!(expected, produced);
+        }
+
+        // Walks are produced one at a time, so iteration can stop part of the way through.
+        let apple = Token::Ingredient("apple".to_string());
+        let banana = Token::Ingredient("banana".to_string());
+        let elderberry = Token::Ingredient("elderberry".to_string());
+        let date = Token::Ingredient("date".to_string());
+        let fig = Token::Ingredient("fig".to_string());
+        let grape = Token::Ingredient("grape".to_string());
+
+        let mut walks = nested.iter_walks(vec![1, 2]).expect("Unable to iterate walks");
+        assert_eq!(Some(vec![&apple, &banana, &date, &fig, &grape]), walks.next());
+        assert_eq!(Some(vec![&apple, &elderberry, &fig, &grape]), walks.next());
+        assert_eq!(None, walks.next());
+
+        assert_eq!(2, nested.depth());
+        assert!(nested.iter_walks(vec![0]).is_err());
+        assert!(nested.iter_walks(vec![0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_variant_count() {
+        let token = |s: &str| FlowItem::Token(Token::Ingredient(s.to_string()));
+
+        let flow = flow![
+            token("apple"),
+            FlowItem::Split(splitset![
+                Split::new(flow![token("banana"), FlowItem::Split(splitset![
+                    Split::new(flow![token("cherry")], allow![0]),
+                    Split::new(flow![token("date")], allow![0, 1]),
+                ])], allow![0, 2]),
+                Split::new(flow![token("elderberry")], allow![1, 2]),
+            ]),
+            FlowItem::Split(splitset![
+                Split::new(flow![token("fig")], allow![2]),
+                Split::new(flow![token("grape")], allow![1, 2]),
+            ]),
+        ];
+
+        for slot_stack in &[vec![0 as Slot, 0], vec![1, 0], vec![2, 0], vec![0, 2], vec![1, 2], vec![3, 3]] {
+            let expected = flow.iter_walks(slot_stack.clone()).expect("Unable to iterate walks").count() as u64;
+            let produced = flow.variant_count(slot_stack).expect("Unable to count walks");
+            assert_eq!(expected, produced);
+        }
+
+        assert_eq!(6, flow.variant_count(&[0, 2]).expect("Unable to count walks"));
+        assert!(flow.variant_count(&[2]).is_err());
+
+        // Taking the banana, any of three inner branches can be followed by fig, grape or neither;
+        // taking the elderberry, only fig or grape can follow; and taking neither, neither can follow.
+        assert_eq!(3 * 3 + 2 + 1, flow.total_variant_count());
+        assert_eq!(1, flow![token("apple")].total_variant_count());
+
+        // Slots allowed by more than one branch do not make the same walk count twice.
+        let overlapping = flow![
+            FlowItem::Split(splitset![
+                Split::new(flow![token("fig")], allow![0, 1]),
+                Split::new(flow![token("grape")], allow![1, 2]),
+            ]),
+            FlowItem::Split(splitset![
+                Split::new(flow![token("apple")], allow![0, 1, 2]),
+                Split::new(flow![token("banana")], allow![1]),
+            ]),
+        ];
+
+        for flow in &[flow, overlapping] {
+            assert_eq!(flow.variants().count() as u64, flow.total_variant_count());
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let apple = Token::Ingredient("apple".to_string());
+        let banana = Token::Ingredient("banana".to_string());
+        let cherry = Token::Ingredient("cherry".to_string());
+        let token = |t: &Token| FlowItem::Token(t.clone());
+
+        let flow = flow![
+            token(&apple),
+            FlowItem::Split(splitset![
+                Split::new(flow![token(&banana), FlowItem::Split(splitset![
+                    Split::new(flow![token(&cherry)], allow![0]),
+                ])], allow![1]),
+                Split::new(flow![token(&cherry)], allow![2]),
+            ]),
+        ];
+
+        let produced: Vec<_> = flow.variants().map(|v| (v.slot_stack, v.walk)).collect();
+        let expected = vec![
+            (vec![0 as Slot, 0], vec![&apple]),
+            (vec![1, 1], vec![&apple, &banana]),
+            (vec![0, 1], vec![&apple, &banana, &cherry]),
+            (vec![0, 2], vec![&apple, &cherry]),
+        ];
+
+        assert_eq!(expected, produced);
+        assert_eq!(1, flow![token(&apple)].variants().count());
+
+        // The slot stack given with each variant selects its walk.
+        for variant in flow.variants() {
+            let walks: Vec<_> = flow.iter_walks(variant.slot_stack).expect("Unable to iterate walks").collect();
+            assert!(walks.contains(&variant.walk));
+        }
+    }
+
+    #[test]
+    fn test_axes() {
+        let apple = Token::Ingredient("apple".to_string());
+        let bacon = Token::Ingredient("bacon".to_string());
+        let cherry = Token::Ingredient("cherry".to_string());
+        let date = Token::Ingredient("date".to_string());
+        let token = |t: &Token| FlowItem::Token(t.clone());
+        let diet = |g: Gate| Condition::on("diet", g);
+        let method = |g: Gate| Condition::on("method", g);
+        let assign = |d: Slot, m: Slot| -> Assignment { btreemap!["diet".to_string() => d, "method".to_string() => m] };
+
+        // The split on axes takes up no level of nesting, so the split within it is chosen by the outermost slot.
+        let flow = flow![
+            token(&apple),
+            FlowItem::Split(splitset![
+                Split::conditional(flow![token(&bacon)], Condition::all(vec![diet(allow![1]), method(block![0])])),
+                Split::conditional(flow![FlowItem::Split(splitset![
+                    Split::new(flow![token(&cherry)], allow![0]),
+                    Split::new(flow![token(&date)], allow![1]),
+                ])], Condition::any(vec![diet(allow![0]), method(allow![0])])),
+            ]),
+        ];
+
+        assert_eq!(1, flow.depth());
+        assert_eq!(btreemap!["diet".to_string() => btreeset![0, 1], "method".to_string() => btreeset![0]], flow.axes());
+        assert_eq!(3 * 2, flow.assignments().len());
+
+        let inputs_and_expected = vec![
+            ((vec![0 as Slot], assign(1, 1)), vec![vec![&apple, &bacon]]),
+            ((vec![0], assign(0, 1)), vec![vec![&apple, &cherry]]),
+            ((vec![1], assign(1, 0)), vec![vec![&apple, &date]]),
+            ((vec![2], assign(0, 0)), vec![vec![&apple]]),
+            ((vec![0], assign(2, 1)), vec![vec![&apple]]),
+        ];
+
+        for ((slot_stack, assignment), expected) in inputs_and_expected {
+            let produced: Vec<_> = flow.iter_walks_with(slot_stack.clone(), &assignment).expect("Unable to iterate walks").collect();
+            assert_eq!(expected, produced);
+
+            let count = flow.variant_count_with(&slot_stack, &assignment).expect("Unable to count walks");
+            assert_eq!(expected.len() as u64, count);
+        }
+
+        // Every axis in the flow must be given a choice.
+        assert!(flow.iter_walks(vec![0]).is_err());
+        assert!(flow.iter_walks_with(vec![0], &btreemap!["diet".to_string() => 0]).is_err());
+        assert!(flow.find_walks(&mut vec![0]).is_err());
+        assert!(flow.variants_with(vec![btreemap!["diet".to_string() => 0]]).is_err());
+
+        let produced: Vec<_> = flow.variants().map(|v| v.walk).collect();
+        // Skipping the inner split and taking neither conditional branch are different variants, despite the same tokens.
+        let expected = vec![vec![&apple], vec![&apple, &bacon], vec![&apple], vec![&apple, &cherry], vec![&apple, &date]];
+        assert_eq!(expected, produced);
+        assert_eq!(5, flow.total_variant_count());
+
+        // A split on axes is given a branch that is taken when none of its conditions hold.
+        let produced = SplitSet::normalize_splits(vec![
+            Split::conditional(flow![token(&apple)], diet(allow![0])),
+            Split::conditional(flow![token(&apple)], method(allow![1])),
+        ]);
+        let either = Condition::any(vec![diet(allow![0]), method(allow![1])]);
+        let expected = btreeset![
+            Split::conditional(flow![token(&apple)], either.clone()),
+            Split::conditional(flow![], Condition::negate(Condition::any(vec![either]))),
+        ];
+        assert_eq!(expected, produced);
+    }
+
     #[test]
     fn test_normalize_splits() {
         let token_a = Token::Ingredient("apple".to_string());
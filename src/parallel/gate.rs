@@ -8,6 +8,7 @@ use std::borrow::Cow;
 pub type Slot = u8;
 pub type SlotSet = BTreeSet<Slot>;
 
+#[cfg_attr(not(test), allow(unused_macros))]
 macro_rules! allow {
     ( $($slot:expr),* $(,)? ) => (Gate::allow(vec!($($slot),*)));
 }
@@ -33,9 +34,9 @@ impl GateType {
     }
 
     pub fn invert(&self) -> Self {
-        match self {
-            &GateType::Allow => GateType::Block,
-            &GateType::Block => GateType::Allow,
+        match *self {
+            GateType::Allow => GateType::Block,
+            GateType::Block => GateType::Allow,
         }
     }
 }
@@ -165,7 +166,7 @@ impl Gate {
     /// Combines two gates using a symmetric difference operation.
     /// The resulting gate allows any slots allowed by exactly one of the input gates.
     pub fn sym_difference(&self, gate: &Self) -> Self {
-        let sym_diff_slots = self.slots().symmetric_difference(&gate.slots()).cloned();
+        let sym_diff_slots = self.slots().symmetric_difference(gate.slots()).cloned();
 
         match (self.0, gate.0) {
             (GateType::Allow, GateType::Allow) | (GateType::Block, GateType::Block) => Gate::allow(sym_diff_slots),
@@ -3,3 +3,5 @@
 pub mod gate;
 pub mod walk;
 pub mod flow;
+pub mod names;
+pub mod axis;
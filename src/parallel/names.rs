@@ -0,0 +1,129 @@
+use failure::Error;
+
+use super::gate::Slot;
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum NameError {
+    #[fail(display = "variant names must start with a letter, and contain only letters, digits, '-' and '_'; found: {}", name)]
+    Invalid {
+        name: String,
+    },
+
+    #[fail(display = "variant name declared more than once: {}", name)]
+    Duplicate {
+        name: String,
+    },
+
+    #[fail(display = "no slots are left for variant name: {}", name)]
+    TooMany {
+        name: String,
+    },
+}
+
+/// Names for the slots of a recipe, e.g. "vegan" or "grill", so that gates and walks need not use bare slot numbers.
+/// Each declared name is given the next unused slot, starting from 0.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct VariantNames(Vec<String>);
+
+impl VariantNames {
+    pub fn new() -> Self {
+        VariantNames::default()
+    }
+
+    pub fn is_name_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '-' || c == '_'
+    }
+
+    /// Whether a name starts with a letter, and is made up only of name characters.
+    pub fn is_valid_name(name: &str) -> bool {
+        name.chars().next().is_some_and(char::is_alphabetic) && name.chars().all(VariantNames::is_name_char)
+    }
+
+    /// Declares a new name, returning the slot it was given.
+    pub fn declare<S: Into<String>>(&mut self, name: S) -> Result<Slot, Error> {
+        let name = name.into();
+
+        if !VariantNames::is_valid_name(&name) {
+            Err(NameError::Invalid{name: name.clone()})?;
+        }
+        else if self.slot(&name).is_some() {
+            Err(NameError::Duplicate{name: name.clone()})?;
+        }
+        else if self.0.len() > usize::from(Slot::MAX) {
+            Err(NameError::TooMany{name: name.clone()})?;
+        }
+
+        self.0.push(name);
+        Ok((self.0.len() - 1) as Slot)
+    }
+
+    pub fn slot(&self, name: &str) -> Option<Slot> {
+        self.0.iter().position(|n| n == name).map(|i| i as Slot)
+    }
+
+    pub fn name(&self, slot: Slot) -> Option<&str> {
+        self.0.get(usize::from(slot)).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Describes a slot stack by name, from the outermost level of nesting inwards.
+    /// Slots without a name are given by number.
+    pub fn label(&self, slot_stack: &[Slot]) -> Vec<String> {
+        slot_stack.iter()
+            .rev()
+            .map(|&slot| self.name(slot).map_or_else(|| slot.to_string(), str::to_string))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NameError, VariantNames};
+
+    #[test]
+    fn test_declare() {
+        let inputs_and_expected = vec![
+            (vec!["vegan", "gluten-free", "grill"], Ok(vec![0, 1, 2])),
+            (vec!["vegan", "vegan"], Err(NameError::Duplicate{name: "vegan".to_string()})),
+            (vec!["2nd"], Err(NameError::Invalid{name: "2nd".to_string()})),
+            (vec!["no dairy"], Err(NameError::Invalid{name: "no dairy".to_string()})),
+            (vec![""], Err(NameError::Invalid{name: "".to_string()})),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut names = VariantNames::new();
+            let produced = input.into_iter()
+                .map(|n| names.declare(n))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.downcast::<NameError>().expect("Unexpected error type"));
+            assert_eq!(expected, produced);
+        }
+
+        let mut names = VariantNames::new();
+        for i in 0..256 {
+            names.declare(format!("v{}", i)).expect("Unable to declare");
+        }
+
+        let produced = names.declare("one-too-many").unwrap_err().downcast::<NameError>().expect("Unexpected error type");
+        assert_eq!(NameError::TooMany{name: "one-too-many".to_string()}, produced);
+    }
+
+    #[test]
+    fn test_label() {
+        let mut names = VariantNames::new();
+        names.declare("vegan").expect("Unable to declare");
+        names.declare("grill").expect("Unable to declare");
+
+        assert_eq!(Some(1), names.slot("grill"));
+        assert_eq!(None, names.slot("oven"));
+        assert_eq!(Some("vegan"), names.name(0));
+        assert_eq!(vec!["grill", "vegan", "7"], names.label(&[7, 0, 1]));
+    }
+}
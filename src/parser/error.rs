@@ -0,0 +1,243 @@
+use std::fmt;
+
+use failure::{Error, Fail};
+
+use recipe::HeaderError;
+use super::*;
+use super::recover::Recovery;
+
+// Keywords that may follow each token sigil, along with a description of their expected value, if any.
+const CONCRETE_KEYWORDS: &[(&str, Option<&str>)] = &[
+    (TOOL_KEYWORD, Some("a phrase")),
+    (CONTAINER_KEYWORD, Some("a phrase")),
+    (APPLIANCE_KEYWORD, Some("a phrase")),
+];
+
+const OPERATOR_KEYWORDS: &[(&str, Option<&str>)] = &[
+    (TRANSFER_KEYWORD, Some("a phrase")),
+    (MEASURE_KEYWORD, Some("a quantity, such as '2 cups'")),
+    (TAKE_KEYWORD, Some("a portion, such as '1/3' or '200 g'")),
+    (LEAVE_KEYWORD, Some("a portion, such as '1/3' or '200 g'")),
+    (PLACE_KEYWORD, None),
+    (REMOVE_KEYWORD, None),
+    (CONFIGURE_KEYWORD, Some("a phrase")),
+    (MELD_KEYWORD, Some("a phrase")),
+    (DISCARD_KEYWORD, None),
+    (EMPTY_KEYWORD, None),
+];
+
+const METADATA_KEYWORDS: &[(&str, Option<&str>)] = &[
+    (TAG_SET_KEYWORD, Some("a tag name")),
+    (TAG_GET_KEYWORD, Some("a tag name")),
+];
+
+/// A failure to parse recipe source, pointing at the offending line and column.
+/// Lines and columns are 1-based, and columns count characters rather than bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    snippet: String,
+    expected: String,
+}
+
+impl ParseError {
+    /// Creates an error at a byte offset into the source.
+    pub fn at<S: Into<String>>(source: &str, offset: usize, expected: S) -> Self {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..].find('\n').map_or(source.len(), |i| offset + i);
+
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            snippet: source[line_start..line_end].trim_end_matches('\r').to_string(),
+            expected: expected.into(),
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        // Keep tabs in the underline, so that the caret lines up with the snippet.
+        let padding: String = self.snippet.chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "error: {}", self.expected)?;
+        writeln!(f, "{}--> line {}, column {}", gutter, self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.snippet)?;
+        write!(f, "{} | {}^", gutter, padding)
+    }
+}
+
+impl Fail for ParseError {}
+
+pub(super) fn offset(source: &str, rest: &str) -> usize {
+    source.len() - rest.len()
+}
+
+/// Finds the first error in source that failed to parse as a recipe.
+pub fn diagnose(source: &str) -> ParseError {
+    let (_, diagnostics) = Recovery::new(source).recipe();
+
+    diagnostics.into_iter().next()
+        .unwrap_or_else(|| ParseError::at(source, source.len(), "expected the end of the recipe"))
+}
+
+pub(super) fn diagnose_header_field(source: &str, key_offset: usize, error: Error) -> ParseError {
+    let expected = match error.downcast::<HeaderError>() {
+        Ok(HeaderError::UnknownField{..}) =>
+            "expected one of the header fields 'title', 'yield', 'servings', 'author', 'source', 'tags', 'variants' or 'axis'".to_string(),
+        Ok(HeaderError::DuplicateField{key}) =>
+            format!("expected the header field '{}' to be given only once", key),
+        Ok(HeaderError::InvalidValue{ref key, ..}) if key.eq_ignore_ascii_case("variants") =>
+            format!("expected a comma-separated list of distinct names, such as 'vegan, gluten-free', for the header field '{}'", key),
+        Ok(HeaderError::InvalidValue{ref key, ..}) if key.eq_ignore_ascii_case("axis") =>
+            format!("expected a new axis name, a ':' and a comma-separated list of distinct choices, such as 'diet: vegan, omnivore', for the header field '{}'", key),
+        Ok(HeaderError::InvalidValue{key, ..}) =>
+            format!("expected a number or range for the header field '{}'", key),
+        Err(error) => error.to_string(),
+    };
+
+    ParseError::at(source, key_offset, expected)
+}
+
+/// Whether a successfully parsed token is actually a malformed concrete keyword token, e.g. "*tool: !!",
+/// which would otherwise be read as an ingredient.
+pub(super) fn is_misread_keyword(token: &Token, rest: &str) -> bool {
+    match *token {
+        Token::Ingredient(ref name) => rest.starts_with(KEYWORD_SEP_SIGIL) && CONCRETE_KEYWORDS.iter().any(|&(k, _)| k == name),
+        _ => false,
+    }
+}
+
+pub(super) fn diagnose_token(source: &str, rest: &str, nested: bool) -> ParseError {
+    let sigil = rest.chars().next().unwrap_or_default();
+    let after_sigil = rest[sigil.len_utf8()..].trim_start();
+    let after_sigil_offset = offset(source, after_sigil);
+
+    let keywords = match sigil {
+        CONCRETE_TOKEN_SIGIL => CONCRETE_KEYWORDS,
+        OPERATOR_TOKEN_SIGIL => OPERATOR_KEYWORDS,
+        METADATA_TOKEN_SIGIL => METADATA_KEYWORDS,
+        _ => &[],
+    };
+
+    let word: String = after_sigil.chars().take_while(|c| c.is_alphanumeric()).collect();
+    let keyword = keywords.iter().find(|&&(k, _)| k == word);
+
+    match (sigil, keyword) {
+        (_, Some(&(keyword, Some(value)))) => {
+            let after_keyword = after_sigil[keyword.len()..].trim_start();
+
+            match after_keyword.starts_with(KEYWORD_SEP_SIGIL) {
+                true => {
+                    let value_offset = offset(source, after_keyword[KEYWORD_SEP_SIGIL.len_utf8()..].trim_start());
+                    ParseError::at(source, value_offset, format!("expected {} after '{}{}{}'", value, sigil, keyword, KEYWORD_SEP_SIGIL))
+                },
+                false => ParseError::at(source, offset(source, after_keyword), format!("expected '{}' after '{}{}'", KEYWORD_SEP_SIGIL, sigil, keyword)),
+            }
+        },
+        (OPERATOR_TOKEN_SIGIL, None) | (METADATA_TOKEN_SIGIL, None) => {
+            let names: Vec<_> = keywords.iter().map(|&(k, _)| format!("'{}'", k)).collect();
+            ParseError::at(source, after_sigil_offset, format!("expected one of {} after '{}'", names.join(", "), sigil))
+        },
+        (INGREDIENT_SIGIL, None) | (ACTION_SIGIL, None) | (COMBINATION_SIGIL, None) | (MODIFIER_SIGIL, None) | (ANNOTATION_SIGIL, None) => {
+            ParseError::at(source, after_sigil_offset, format!("expected a phrase after '{}'", sigil))
+        },
+        (VAR_SPLIT_SEP_SIGIL, None) | (VAR_SPLIT_CLOSE_SIGIL, None) | (VAR_SPLIT_TAG_SIGIL, None) if !nested => {
+            ParseError::at(source, offset(source, rest), format!("expected a token, as '{}' is only allowed within a split", sigil))
+        },
+        _ => ParseError::at(source, offset(source, rest), "expected a token, such as '* apple', or a split starting with '['"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseError;
+    use super::super::Parsers;
+
+    #[test]
+    fn test_at() {
+        let source = "* apple\n\t= slice ü\r\n/ mix";
+
+        let inputs_and_expected = vec![
+            (0, (1, 1, "* apple")),
+            (2, (1, 3, "* apple")),
+            (8, (2, 1, "\t= slice ü")),
+            (19, (2, 11, "\t= slice ü")),
+            (source.len(), (3, 6, "/ mix")),
+        ];
+
+        for (input, (line, column, snippet)) in inputs_and_expected {
+            let produced = ParseError::at(source, input, "");
+            assert_eq!((line, column, snippet), (produced.line(), produced.column(), produced.snippet()));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let produced = ParseError::at("* apple\n\t= !!!", 11, "expected a phrase after '='").to_string();
+        let expected = "error: expected a phrase after '='\n --> line 2, column 4\n  |\n2 | \t= !!!\n  | \t  ^";
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_parse_recipe() {
+        let inputs_and_expected = vec![
+            ("* !!!", Some((1, 3, "expected a phrase after '*'"))),
+            ("* apple\n  = slice\n  / !!", Some((3, 5, "expected a phrase after '/'"))),
+            ("+measure: lots", Some((1, 11, "expected a quantity, such as '2 cups' after '+measure:'"))),
+            ("+measure 2 cups", Some((1, 10, "expected ':' after '+measure'"))),
+            ("*tool: !!", Some((1, 8, "expected a phrase after '*tool:'"))),
+            ("&put: dough", Some((1, 2, "expected one of 'set', 'get' after '&'"))),
+            ("* apple ]", Some((1, 9, "expected a token, as ']' is only allowed within a split"))),
+            ("[ * apple #", Some((1, 12, "expected a comma-separated list of slot numbers or declared variant names after '#', such as '#0, 2', \
+                or a condition on declared axes, such as '#diet: vegan and not method: grill'"))),
+            ("@variants: vegan\n[ * tofu #vegan | * beef #keto ]", Some((2, 27, "expected a comma-separated list of slot numbers or declared variant names after '#', such as '#0, 2', \
+                or a condition on declared axes, such as '#diet: vegan and not method: grill'"))),
+            ("@axis: diet: vegan\n[ * tofu #diet: vegan | * beef #1 ]", Some((2, 1, "expected the gates of a split to either all give slots, or all give conditions on axes"))),
+            ("@axis: diet: vegan\n[ * tofu #diet: keto ]", Some((2, 11, "expected a comma-separated list of slot numbers or declared variant names after '#', such as '#0, 2', \
+                or a condition on declared axes, such as '#diet: vegan and not method: grill'"))),
+            ("@axis: diet", Some((1, 2, "expected a new axis name, a ':' and a comma-separated list of distinct choices, such as 'diet: vegan, omnivore', for the header field 'axis'"))),
+            ("* apple\n[ * banana | = slice", Some((2, 21, "expected ']' to close the split opened at line 2, column 1"))),
+            ("[ * banana #1 * cherry ]", Some((1, 15, "expected '|' or ']' after the gate"))),
+            ("[ * banana | [ = !! ] ]", Some((1, 18, "expected a phrase after '='"))),
+            ("@title: Pie\n@title: Tart", Some((2, 2, "expected the header field 'title' to be given only once"))),
+            ("@servings: many", Some((1, 2, "expected a number or range for the header field 'servings'"))),
+            ("@calories: 500", Some((1, 2, "expected one of the header fields 'title', 'yield', 'servings', 'author', 'source', 'tags', 'variants' or 'axis'"))),
+            ("@variants: vegan, vegan", Some((1, 2, "expected a comma-separated list of distinct names, such as 'vegan, gluten-free', for the header field 'variants'"))),
+            ("@title", Some((1, 2, "expected a header field of the form '@name: value'"))),
+            ("12 apples", Some((1, 1, "expected a token, such as '* apple', or a split starting with '['"))),
+            ("@title: Pie\n* apple\n---\n!!!", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::parse_recipe(input).err().map(|e| (e.line(), e.column(), e.expected().to_string()));
+            let expected = expected.map(|(l, c, e)| (l, c, e.to_string()));
+            assert_eq!(expected, produced);
+        }
+    }
+}
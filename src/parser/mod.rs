@@ -1,28 +1,65 @@
+mod error;
+mod recover;
+
 use std::str::FromStr;
 
 use nom;
 
 use token::Token;
-use parallel::flow::{Flow, FlowItem, Split, SplitSet};
+use types::Portion;
+use types::quantity::{Quantity, Magnitude};
+use types::unit::{Amount, Unit, parse_amount, vulgar_fraction_value};
+use parallel::axis::{AxisError, Condition};
+use parallel::flow::{Comment, Flow, FlowItem, Split, SplitSet};
 use parallel::gate::{Gate, Slot};
+use parallel::names::VariantNames;
+use recipe::{Header, Recipe};
+
+pub use self::error::ParseError;
 
 const INGREDIENT_SIGIL: char = '*';
 const MODIFIER_SIGIL: char = ',';
 const ANNOTATION_SIGIL: char = ';';
 const ACTION_SIGIL: char = '=';
 const COMBINATION_SIGIL: char = '/';
+const KEYWORD_SEP_SIGIL: char = ':';
+const QUOTE_CHAR: char = '"';
+const ESCAPE_CHAR: char = '\\';
+const PHRASE_PUNCTUATION_CHARS: &str = "-'’.";
+const RANGE_SIGILS: &str = "-–";
+const VULGAR_FRACTION_CHARS: &str = "½⅓⅔¼¾⅕⅖⅗⅘⅙⅚⅐⅛⅜⅝⅞⅑⅒";
+
+const PARTITION_KEEP_SIGIL: char = '+';
+const PARTITION_LEAVE_SIGIL: char = '-';
+
+const TOOL_KEYWORD: &str = "tool";
+const CONTAINER_KEYWORD: &str = "container";
+const APPLIANCE_KEYWORD: &str = "appliance";
+
+const TRANSFER_KEYWORD: &str = "transfer";
+const MEASURE_KEYWORD: &str = "measure";
+const TAKE_KEYWORD: &str = "take";
+const LEAVE_KEYWORD: &str = "leave";
+const PLACE_KEYWORD: &str = "place";
+const REMOVE_KEYWORD: &str = "remove";
+const CONFIGURE_KEYWORD: &str = "configure";
+const MELD_KEYWORD: &str = "meld";
+const DISCARD_KEYWORD: &str = "discard";
+const EMPTY_KEYWORD: &str = "empty";
+
+const TAG_SET_KEYWORD: &str = "set";
+const TAG_GET_KEYWORD: &str = "get";
 
 const CONCRETE_TOKEN_SIGIL: char = '*';
 const OPERATOR_TOKEN_SIGIL: char = '+';
 const METADATA_TOKEN_SIGIL: char = '&';
 
-const SPLIT_SET_START: char = '[';
-const SPLIT_SET_CLOSE: char = ']';
-const SPLIT_SET_SEPARATOR: char = '|';
-const GATE_START: char = '<';
-const GATE_CLOSE: char = '>';
-const GATE_INVERT_FLAG: char = '!';
-const EMPTY_FLOW_FLAG: char = '~';
+const LINE_COMMENT_SIGIL: char = '%';
+const BLOCK_COMMENT_START: &str = "%{";
+const BLOCK_COMMENT_CLOSE: &str = "}%";
+
+const HEADER_FIELD_SIGIL: char = '@';
+const NOTES_SEPARATOR: &str = "---";
 
 const VAR_SPLIT_START_SIGIL: char = '[';
 const VAR_SPLIT_CLOSE_SIGIL: char = ']';
@@ -31,11 +68,17 @@ const VAR_SPLIT_TAG_SIGIL: char = '#';
 const VAR_SPLIT_SLOT_SEP_SIGIL: char = ',';
 const VAR_SPLIT_INV_SLOT_FLAG_SIGIL: char = '!';
 
+const CONDITION_START_SIGIL: char = '(';
+const CONDITION_CLOSE_SIGIL: char = ')';
+const CONDITION_AND_KEYWORD: &str = "and";
+const CONDITION_OR_KEYWORD: &str = "or";
+const CONDITION_NOT_KEYWORD: &str = "not";
+
 pub struct Parsers;
 
 impl Parsers {
 
-    /** Primitive types **/
+    /* Primitive types */
 
     named!(pub integer_repr<&str, &str>,
         recognize!(nom::digit)
@@ -84,32 +127,246 @@ impl Parsers {
         )))
     );
 
-    named!(pub phrase<&str, &str>,
-        // A sequence of whitespace-separated alphanumerics.
-        ws!(recognize!(separated_nonempty_list_complete!(nom::space, nom::alphanumeric)))
+    // A double-quoted string, which may contain any character. Quotes and backslashes are escaped
+    // with a backslash, as are newlines and tabs ("\n" and "\t").
+    pub fn quoted_string(input: &str) -> nom::IResult<&str, String> {
+        let mut chars = input.char_indices();
+
+        match chars.next() {
+            Some((_, QUOTE_CHAR)) => {},
+            Some(_) => return nom::IResult::Error(nom::ErrorKind::Char),
+            None => return nom::IResult::Incomplete(nom::Needed::Size(1)),
+        }
+
+        let mut value = String::new();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                QUOTE_CHAR => return nom::IResult::Done(&input[i + c.len_utf8()..], value),
+                ESCAPE_CHAR => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, e)) if e == QUOTE_CHAR || e == ESCAPE_CHAR => value.push(e),
+                    Some(_) => return nom::IResult::Error(nom::ErrorKind::Escaped),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+
+        nom::IResult::Incomplete(nom::Needed::Unknown)
+    }
+
+    // A run of Unicode letters and digits, along with any punctuation that may appear within a name.
+    named!(pub bare_word<&str, &str>,
+        take_while1!(|c: char| c.is_alphanumeric() || PHRASE_PUNCTUATION_CHARS.contains(c))
+    );
+
+    named!(pub phrase_word<&str, String>,
+        add_return_error!(nom::ErrorKind::AlphaNumeric, alt!(
+            complete!(call!(Self::quoted_string))
+            | map!(call!(Self::bare_word), String::from)
+        ))
     );
 
-    named!(pub measurement<&str, &str>,
-        recognize!(
-            char!('X')
+    // A sequence of space-separated words, which does not continue onto the next line.
+    // The spacing between words is preserved.
+    named!(pub phrase<&str, String>,
+        delimited!(
+            opt!(complete!(nom::multispace)),
+            do_parse!(
+                first: call!(Self::phrase_word) >>
+                rest: many0!(complete!(pair!(call!(nom::space), call!(Self::phrase_word)))) >>
+                (rest.into_iter().fold(first, |mut acc, (sep, word)| {
+                    acc.push_str(sep);
+                    acc.push_str(&word);
+                    acc
+                }))
+            ),
+            opt!(complete!(nom::multispace))
         )
     );
 
-    /// Represents a fractional amount between 0 and 1, noninclusive.
-    named!(pub f_partition<&str, (usize, usize)>,
-        tuple!(
-            map!(many1!(char!('+')), |c| c.len()),
-            map!(many1!(char!('-')), |c| c.len())
+    named!(pub integer<&str, Amount>,
+        map_opt!(call!(Self::integer_repr), parse_amount)
+    );
+
+    named!(pub decimal<&str, Amount>,
+        map_opt!(call!(Self::decimal_repr), parse_amount)
+    );
+
+    named!(pub rational<&str, Amount>,
+        map_opt!(call!(Self::rational_repr), parse_amount)
+    );
+
+    named!(pub vulgar_fraction<&str, Amount>,
+        map_opt!(one_of!(VULGAR_FRACTION_CHARS), vulgar_fraction_value)
+    );
+
+    // A whole number followed by a fraction, e.g. "1 1/2" or "1½".
+    named!(pub mixed_number<&str, Amount>,
+        complete!(do_parse!(
+            whole: call!(Self::integer) >>
+            fract: alt!(
+                complete!(preceded!(opt!(nom::space), call!(Self::rational)))
+                | complete!(preceded!(opt!(nom::space), call!(Self::vulgar_fraction)))
+            ) >>
+            (whole + fract)
+        ))
+    );
+
+    named!(pub amount<&str, Amount>,
+        alt!(
+            call!(Self::mixed_number)
+            | complete!(call!(Self::rational))
+            | complete!(call!(Self::decimal))
+            | complete!(call!(Self::vulgar_fraction))
+            | complete!(call!(Self::integer))
         )
     );
 
-    /** Tokens **/
+    // Either a single amount, or an increasing range of amounts such as "2-3".
+    named!(pub magnitude<&str, Magnitude>,
+        alt!(
+            complete!(do_parse!(
+                low: call!(Self::amount) >>
+                opt!(nom::space) >>
+                one_of!(RANGE_SIGILS) >>
+                opt!(nom::space) >>
+                high: verify!(call!(Self::amount), |high| low < high) >>
+                (Magnitude::range(low, high))
+            ))
+            | map!(call!(Self::amount), Magnitude::Exact)
+        )
+    );
+
+    named!(pub unit_word<&str, &str>,
+        take_while1!(|c: char| c.is_alphabetic() || c == '°')
+    );
+
+    // Units may span up to three words, e.g. "imp fl oz"; the longest match wins.
+    named!(pub unit<&str, Unit>,
+        alt!(
+            complete!(map_res!(
+                recognize!(tuple!(
+                    call!(Self::unit_word), call!(nom::space), call!(Self::unit_word), call!(nom::space), call!(Self::unit_word)
+                )),
+                Unit::from_str
+            ))
+            | complete!(map_res!(
+                recognize!(tuple!(call!(Self::unit_word), call!(nom::space), call!(Self::unit_word))),
+                Unit::from_str
+            ))
+            | complete!(map_res!(call!(Self::unit_word), Unit::from_str))
+        )
+    );
+
+    named!(pub approximate_flag<&str, bool>,
+        map!(
+            opt!(alt!(
+                complete!(tag!("~"))
+                | complete!(tag!("about"))
+                | complete!(tag!("approximately"))
+                | complete!(tag!("approx."))
+            )),
+            |o| o.is_some()
+        )
+    );
+
+    // A magnitude with an optional unit; amounts without a unit are counts.
+    named!(pub quantity<&str, Quantity>,
+        ws!(do_parse!(
+            approximate: call!(Self::approximate_flag) >>
+            magnitude: call!(Self::magnitude) >>
+            unit: opt!(complete!(call!(Self::unit))) >>
+            ({
+                let quantity = Quantity::with_magnitude(magnitude, unit.unwrap_or(Unit::Each));
+                match approximate {
+                    true => quantity.into_approximate(),
+                    false => quantity,
+                }
+            })
+        ))
+    );
+
+    // Represents a fractional amount between 0 and 1, noninclusive, as a run of parts kept ('+')
+    // followed by a run of parts left over ('-'). For example, "+--" is one third.
+    named!(pub f_partition<&str, Portion>,
+        map_res!(
+            complete!(tuple!(
+                map!(many1!(char!(PARTITION_KEEP_SIGIL)), |c| c.len()),
+                map!(many1!(char!(PARTITION_LEAVE_SIGIL)), |c| c.len())
+            )),
+            |(k, l): (usize, usize)| Portion::fraction(k as u64, (k + l) as u64)
+        )
+    );
+
+    // A fraction between 0 and 1, noninclusive, written as a ratio, e.g. "1/3".
+    named!(pub f_rational<&str, Portion>,
+        map_res!(
+            call!(Self::nz_rational_repr),
+            |r: &str| {
+                let (numer, denom) = r.split_at(r.find('/').unwrap_or(0));
+                Portion::fraction(numer.parse().unwrap_or(0), denom[1..].parse().unwrap_or(0))
+            }
+        )
+    );
+
+    named!(pub portion<&str, Portion>,
+        ws!(alt!(
+            complete!(call!(Self::f_partition))
+            // A ratio followed by a unit is a quantity, e.g. "1/3 cup". Without a unit it must be a fraction,
+            // so one out of range, e.g. "4/3", is an error rather than a count.
+            | complete!(preceded!(
+                peek!(terminated!(
+                    call!(Self::rational_repr),
+                    not!(complete!(preceded!(opt!(nom::space), call!(Self::unit))))
+                )),
+                return_error!(nom::ErrorKind::MapRes, call!(Self::f_rational))
+            ))
+            | complete!(map!(call!(Self::quantity), Portion::Quantity))
+            | complete!(map!(call!(Self::phrase), Portion::Pseudo))
+        ))
+    );
+
+    /* Tokens */
 
     named!(pub ingredient_token<&str, Token>,
         ws!(do_parse!(
             char!(INGREDIENT_SIGIL) >>
             value: call!(Self::phrase) >>
-            (Token::Ingredient(value.to_string()))
+            (Token::Ingredient(value))
+        ))
+    );
+
+    // Concrete tokens other than ingredients are spelled out with a keyword, e.g. "*tool: whisk".
+    named!(pub tool_token<&str, Token>,
+        ws!(do_parse!(
+            char!(CONCRETE_TOKEN_SIGIL) >>
+            tag!(TOOL_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Tool(value))
+        ))
+    );
+
+    named!(pub container_token<&str, Token>,
+        ws!(do_parse!(
+            char!(CONCRETE_TOKEN_SIGIL) >>
+            tag!(CONTAINER_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Container(value))
+        ))
+    );
+
+    named!(pub appliance_token<&str, Token>,
+        ws!(do_parse!(
+            char!(CONCRETE_TOKEN_SIGIL) >>
+            tag!(APPLIANCE_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Appliance(value))
         ))
     );
 
@@ -117,7 +374,7 @@ impl Parsers {
         ws!(do_parse!(
             char!(ACTION_SIGIL) >>
             value: call!(Self::phrase) >>
-            (Token::Verb(value.to_string()))
+            (Token::Verb(value))
         ))
     );
 
@@ -125,7 +382,7 @@ impl Parsers {
         ws!(do_parse!(
             char!(COMBINATION_SIGIL) >>
             value: call!(Self::phrase) >>
-            (Token::Combine(value.to_string()))
+            (Token::Combine(value))
         ))
     );
 
@@ -133,7 +390,7 @@ impl Parsers {
         ws!(do_parse!(
             char!(MODIFIER_SIGIL) >>
             value: call!(Self::phrase) >>
-            (Token::Modifier(value.to_string()))
+            (Token::Modifier(value))
         ))
     );
 
@@ -141,78 +398,454 @@ impl Parsers {
         ws!(do_parse!(
             char!(ANNOTATION_SIGIL) >>
             value: call!(Self::phrase) >>
-            (Token::Annotation(value.to_string()))
+            (Token::Annotation(value))
+        ))
+    );
+
+    named!(pub transfer_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(TRANSFER_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Transfer(value))
+        ))
+    );
+
+    named!(pub measure_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(MEASURE_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::quantity) >>
+            (Token::Measure(value))
+        ))
+    );
+
+    named!(pub take_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(TAKE_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::portion) >>
+            (Token::Take(value))
+        ))
+    );
+
+    named!(pub leave_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(LEAVE_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::portion) >>
+            (Token::Leave(value))
+        ))
+    );
+
+    named!(pub place_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(PLACE_KEYWORD) >>
+            not!(call!(nom::alphanumeric)) >>
+            (Token::Place)
+        ))
+    );
+
+    named!(pub remove_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(REMOVE_KEYWORD) >>
+            not!(call!(nom::alphanumeric)) >>
+            (Token::Remove)
+        ))
+    );
+
+    named!(pub configure_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(CONFIGURE_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Configure(value))
+        ))
+    );
+
+    named!(pub meld_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(MELD_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::Meld(value))
+        ))
+    );
+
+    named!(pub discard_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(DISCARD_KEYWORD) >>
+            not!(call!(nom::alphanumeric)) >>
+            (Token::Discard)
+        ))
+    );
+
+    named!(pub empty_token<&str, Token>,
+        ws!(do_parse!(
+            char!(OPERATOR_TOKEN_SIGIL) >>
+            tag!(EMPTY_KEYWORD) >>
+            not!(call!(nom::alphanumeric)) >>
+            (Token::Empty)
+        ))
+    );
+
+    named!(pub tag_set_token<&str, Token>,
+        ws!(do_parse!(
+            char!(METADATA_TOKEN_SIGIL) >>
+            tag!(TAG_SET_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::TagSet(value))
         ))
     );
 
+    named!(pub tag_get_token<&str, Token>,
+        ws!(do_parse!(
+            char!(METADATA_TOKEN_SIGIL) >>
+            tag!(TAG_GET_KEYWORD) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: call!(Self::phrase) >>
+            (Token::TagGet(value))
+        ))
+    );
+
+    // Keyworded tokens are tried first, as concrete keywords share their sigil with ingredients.
     named!(pub token<&str, Token>,
         alt!(
-            call!(Self::ingredient_token)
+            complete!(call!(Self::tool_token))
+            | complete!(call!(Self::container_token))
+            | complete!(call!(Self::appliance_token))
+            | call!(Self::ingredient_token)
             | call!(Self::action_token)
             | call!(Self::combination_token)
             | call!(Self::modifier_token)
             | call!(Self::annotation_token)
+            | complete!(call!(Self::transfer_token))
+            | complete!(call!(Self::measure_token))
+            | complete!(call!(Self::take_token))
+            | complete!(call!(Self::leave_token))
+            | complete!(call!(Self::place_token))
+            | complete!(call!(Self::remove_token))
+            | complete!(call!(Self::configure_token))
+            | complete!(call!(Self::meld_token))
+            | complete!(call!(Self::discard_token))
+            | complete!(call!(Self::empty_token))
+            | complete!(call!(Self::tag_set_token))
+            | complete!(call!(Self::tag_get_token))
         )
     );
 
-    /** Gates **/
+    /* Gates */
 
     named!(pub slot<&str, Slot>,
         ws!(map_res!(nom::digit, Slot::from_str))
     );
 
+    // A name of a variant, axis or choice, e.g. "gluten-free".
+    named!(pub variant_name<&str, &str>,
+        take_while1!(VariantNames::is_name_char)
+    );
+
+    /// Parses a slot given by a declared variant name, e.g. "vegan".
+    pub fn variant_slot<'a>(input: &'a str, names: &VariantNames) -> nom::IResult<&'a str, Slot> {
+        ws!(input, map_opt!(call!(Self::variant_name), |name| names.slot(name)))
+    }
+
     named!(pub gate<&str, Gate>,
-        ws!(complete!(do_parse!(
+        call!(Self::gate_with, &VariantNames::new())
+    );
+
+    /// Parses a gate whose slots may be given by number, or by any of the declared variant names.
+    pub fn gate_with<'a>(input: &'a str, names: &VariantNames) -> nom::IResult<&'a str, Gate> {
+        ws!(input, complete!(do_parse!(
             char!(VAR_SPLIT_TAG_SIGIL) >>
+            gate: call!(Self::slot_list_with, names) >>
+            (gate)
+        )))
+    }
+
+    // The slots allowed by a gate, or blocked if flagged, e.g. "!0, vegan".
+    pub fn slot_list_with<'a>(input: &'a str, names: &VariantNames) -> nom::IResult<&'a str, Gate> {
+        ws!(input, do_parse!(
             inv_flag: map!(opt!(char!(VAR_SPLIT_INV_SLOT_FLAG_SIGIL)), |o| o.is_some()) >>
-            slots: separated_nonempty_list_complete!(char!(VAR_SPLIT_SLOT_SEP_SIGIL), call!(Self::slot)) >>
+            slots: separated_nonempty_list_complete!(
+                char!(VAR_SPLIT_SLOT_SEP_SIGIL),
+                alt!(call!(Self::slot) | call!(Self::variant_slot, names))
+            ) >>
             (match inv_flag {
                 true => Gate::block(slots),
                 false => Gate::allow(slots),
             })
+        ))
+    }
+
+    // A keyword that is not just the start of a longer name, e.g. "and" but not "android".
+    pub fn condition_keyword<'a>(input: &'a str, keyword: &str) -> nom::IResult<&'a str, &'a str> {
+        preceded!(input,
+            opt!(complete!(nom::multispace)),
+            terminated!(tag!(keyword), not!(complete!(call!(Self::variant_name))))
+        )
+    }
+
+    /// Parses the choices of a declared axis that a condition allows, e.g. "diet: vegan, vegetarian".
+    pub fn axis_gate_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Condition> {
+        let (rest, (axis, choices)) = try_parse!(input, ws!(map_opt!(
+            terminated!(call!(Self::variant_name), char!(KEYWORD_SEP_SIGIL)),
+            |axis| header.axes.choices(axis).map(|choices| (axis, choices))
+        )));
+
+        map!(rest, call!(Self::slot_list_with, choices), |gate| Condition::on(axis, gate))
+    }
+
+    pub fn negation_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Condition> {
+        alt!(input,
+            map!(
+                preceded!(call!(Self::condition_keyword, CONDITION_NOT_KEYWORD), call!(Self::negation_with, header)),
+                Condition::negate
+            )
+            | ws!(delimited!(
+                char!(CONDITION_START_SIGIL),
+                call!(Self::condition_with, header),
+                char!(CONDITION_CLOSE_SIGIL)
+            ))
+            | call!(Self::axis_gate_with, header)
+        )
+    }
+
+    pub fn conjunction_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Condition> {
+        do_parse!(input,
+            first: call!(Self::negation_with, header) >>
+            rest: many0!(complete!(preceded!(
+                call!(Self::condition_keyword, CONDITION_AND_KEYWORD),
+                call!(Self::negation_with, header)
+            ))) >>
+            (Condition::all(Some(first).into_iter().chain(rest)))
+        )
+    }
+
+    /// Parses a boolean combination of the choices of declared axes, e.g. "diet: vegan and not method: grill".
+    /// As usual, "not" binds most tightly and "or" least tightly, and parentheses may be used for grouping.
+    pub fn condition_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Condition> {
+        do_parse!(input,
+            first: call!(Self::conjunction_with, header) >>
+            rest: many0!(complete!(preceded!(
+                call!(Self::condition_keyword, CONDITION_OR_KEYWORD),
+                call!(Self::conjunction_with, header)
+            ))) >>
+            (Condition::any(Some(first).into_iter().chain(rest)))
+        )
+    }
+
+    /// Parses a gate given by a condition on axes, rather than by slots.
+    pub fn condition_gate_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Condition> {
+        ws!(input, complete!(do_parse!(
+            char!(VAR_SPLIT_TAG_SIGIL) >>
+            condition: call!(Self::condition_with, header) >>
+            (condition)
         )))
+    }
+
+    /* Comments */
+
+    // A comment running to the end of the line, e.g. "% use tart apples".
+    named!(pub line_comment<&str, Comment>,
+        do_parse!(
+            not!(complete!(tag!(BLOCK_COMMENT_START))) >>
+            char!(LINE_COMMENT_SIGIL) >>
+            text: take_till!(|c| c == '\n' || c == '\r') >>
+            (Comment::Line(text.trim().to_string()))
+        )
     );
 
-    /** Flows **/
+    // A comment that may span multiple lines, e.g. "%{ ... }%". Its text is kept verbatim.
+    named!(pub block_comment<&str, Comment>,
+        do_parse!(
+            tag!(BLOCK_COMMENT_START) >>
+            text: take_until!(BLOCK_COMMENT_CLOSE) >>
+            tag!(BLOCK_COMMENT_CLOSE) >>
+            (Comment::Block(text.to_string()))
+        )
+    );
 
-    named!(pub flow_item<&str, FlowItem>,
-        alt!(
+    named!(pub comment<&str, Comment>,
+        ws!(alt!(
+            complete!(call!(Self::block_comment))
+            | complete!(call!(Self::line_comment))
+        ))
+    );
+
+    /* Flows */
+
+    named!(pub flow_item<&str, FlowItem<'_>>,
+        call!(Self::flow_item_with, &Header::new())
+    );
+
+    pub fn flow_item_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, FlowItem<'a>> {
+        alt!(input,
             do_parse!(
+                comment: call!(Self::comment) >>
+                (FlowItem::Comment(comment))
+            )
+            | do_parse!(
                 token_val: call!(Self::token) >>
                 (FlowItem::Token(token_val))
             )
             | do_parse!(
-                split_set: call!(Self::split_set) >>
+                split_set: call!(Self::split_set_with, header) >>
                 (FlowItem::Split(split_set))
             )
         )
+    }
+
+    named!(pub flow<&str, Flow<'_>>,
+        call!(Self::flow_with, &Header::new())
     );
 
-    named!(pub flow<&str, Flow>,
-        do_parse!(
-            flow_items: many0!(call!(Self::flow_item)) >>
+    /// Parses a flow whose gates may refer to any of the variant names and axes declared in a header.
+    pub fn flow_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Flow<'a>> {
+        do_parse!(input,
+            flow_items: many0!(call!(Self::flow_item_with, header)) >>
             (Flow::new(flow_items))
         )
+    }
+
+    named!(pub split<&str, Split<'_>>,
+        call!(Self::split_with, &Header::new())
     );
 
-    named!(pub split<&str, Split>,
-        do_parse!(
-            flow: call!(Self::flow) >>
-            gate: map!(opt!(call!(Self::gate)), |g| g.unwrap_or(block!())) >>
-            (Split::new(flow, gate))
+    pub fn split_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, Split<'a>> {
+        do_parse!(input,
+            flow: call!(Self::flow_with, header) >>
+            condition: opt!(call!(Self::condition_gate_with, header)) >>
+            gate: cond!(condition.is_none(), map!(opt!(call!(Self::gate_with, &header.variants)), |g| g.unwrap_or(block!()))) >>
+            (match condition {
+                Some(condition) => Split::conditional(flow, condition),
+                None => Split::new(flow, gate.unwrap_or(block!())),
+            })
         )
-    );
+    }
 
     // A set of splits.
-    named!(pub split_set<&str, SplitSet>,
-        ws!(delimited!(
+    named!(pub split_set<&str, SplitSet<'_>>,
+        call!(Self::split_set_with, &Header::new())
+    );
+
+    pub fn split_set_with<'a>(input: &'a str, header: &Header) -> nom::IResult<&'a str, SplitSet<'a>> {
+        ws!(input, delimited!(
             char!(VAR_SPLIT_START_SIGIL),
-            do_parse!(
-                splits: separated_nonempty_list_complete!(char!(VAR_SPLIT_SEP_SIGIL), call!(Self::split)) >>
-                (SplitSet::new(splits))
+            map_res!(
+                separated_nonempty_list_complete!(char!(VAR_SPLIT_SEP_SIGIL), call!(Self::split_with, header)),
+                Self::checked_split_set
             ),
             char!(VAR_SPLIT_CLOSE_SIGIL)
         ))
+    }
+
+    // Builds a split set, as long as it does not gate some splits by slot and others by axis.
+    fn checked_split_set(splits: Vec<Split<'_>>) -> Result<SplitSet<'_>, AxisError> {
+        let by_axis = splits.iter().any(|s| s.condition().is_some());
+        let by_slot = splits.iter().any(|s| s.condition().is_none() && !s.gate().is_allow_all());
+
+        match by_axis && by_slot {
+            true => Err(AxisError::Mixed),
+            false => Ok(SplitSet::new(splits)),
+        }
+    }
+
+    /* Recipes */
+
+    // A single header line, e.g. "@title: Buttermilk Pancakes".
+    named!(pub header_field<&str, (&str, &str)>,
+        complete!(do_parse!(
+            opt!(nom::multispace) >>
+            char!(HEADER_FIELD_SIGIL) >>
+            key: call!(nom::alpha) >>
+            opt!(nom::space) >>
+            char!(KEYWORD_SEP_SIGIL) >>
+            value: take_till!(|c| c == '\n' || c == '\r') >>
+            ((key, value.trim()))
+        ))
+    );
+
+    named!(pub header<&str, Header>,
+        map_res!(
+            many0!(call!(Self::header_field)),
+            |fields: Vec<(&str, &str)>| {
+                let mut header = Header::new();
+                for (key, value) in fields {
+                    header.set_field(key, value)?;
+                }
+                Ok(header) as Result<Header, ::failure::Error>
+            }
+        )
+    );
+
+    // Free text following a separator line, split into paragraphs on blank lines.
+    named!(pub notes<&str, Vec<String>>,
+        map!(
+            opt!(complete!(preceded!(
+                tuple!(opt!(nom::multispace), tag!(NOTES_SEPARATOR)),
+                call!(nom::rest_s)
+            ))),
+            |text: Option<&str>| {
+                let mut paragraphs: Vec<String> = vec![];
+                let mut current: Vec<&str> = vec![];
+
+                for line in text.unwrap_or("").lines().map(str::trim).chain(Some("")) {
+                    if line.is_empty() {
+                        if !current.is_empty() {
+                            paragraphs.push(current.join(" "));
+                            current.clear();
+                        }
+                    }
+                    else {
+                        current.push(line);
+                    }
+                }
+
+                paragraphs
+            }
+        )
+    );
+
+    /// Parses an entire recipe document, describing where and why parsing failed if it is invalid.
+    pub fn parse_recipe(input: &str) -> Result<Recipe<'_>, ParseError> {
+        match Parsers::recipe(input) {
+            nom::IResult::Done(_, recipe) => Ok(recipe),
+            _ => Err(error::diagnose(input)),
+        }
+    }
+
+    /// Parses as much of a recipe document as possible, skipping over malformed items.
+    /// Returns the partial recipe along with a diagnostic for each skipped item, in source order.
+    pub fn parse_recipe_recovering(input: &str) -> (Recipe<'_>, Vec<ParseError>) {
+        recover::Recovery::new(input).recipe()
+    }
+
+    /// Parses as much of a flow as possible, skipping over malformed items.
+    pub fn parse_flow_recovering(input: &str) -> (Flow<'_>, Vec<ParseError>) {
+        recover::Recovery::new(input).flow_only()
+    }
+
+    // An entire recipe document, which must be consumed in full.
+    named!(pub recipe<&str, Recipe<'_>>,
+        complete!(do_parse!(
+            header: call!(Self::header) >>
+            flow: call!(Self::flow_with, &header) >>
+            notes: call!(Self::notes) >>
+            opt!(complete!(nom::multispace)) >>
+            eof!() >>
+            (Recipe::new(header, flow, notes))
+        ))
     );
 }
 
@@ -220,11 +853,19 @@ impl Parsers {
 mod tests {
     use super::Parsers;
 
-    use nom::{IResult, ErrorKind};
+    use nom::{IResult, ErrorKind, Needed};
+
+    use num_rational::Ratio;
 
     use token::Token;
-    #[macro_use] use parallel::gate::Gate;
-    #[macro_use] use parallel::flow::{Flow, FlowItem, SplitSet, Split};
+    use parallel::axis::Condition;
+    use parallel::gate::Gate;
+    use parallel::flow::{Comment, Flow, FlowItem, Split};
+    use parallel::names::VariantNames;
+    use recipe::Header;
+    use types::Portion;
+    use types::quantity::{Quantity, Magnitude};
+    use types::unit::Unit;
 
     #[test]
     fn test_integer_repr() {
@@ -359,10 +1000,222 @@ mod tests {
             ("apple/007", IResult::Done("/007", "apple")),
             (" a ", IResult::Done("", "a")),
             (" 7 ", IResult::Done("", "7")),
+            ("jalapeño", IResult::Done("", "jalapeño")),
+            ("crème fraîche", IResult::Done("", "crème fraîche")),
+            ("all-purpose flour", IResult::Done("", "all-purpose flour")),
+            ("St. Louis ribs", IResult::Done("", "St. Louis ribs")),
+            ("baker's yeast, instant", IResult::Done(", instant", "baker's yeast")),
+            ("confectioners’ sugar; sifted", IResult::Done("; sifted", "confectioners’ sugar")),
+            ("apple | pear", IResult::Done("| pear", "apple")),
+            ("apple]", IResult::Done("]", "apple")),
+            ("\"salt, kosher\" flakes", IResult::Done("", "salt, kosher flakes")),
+            ("\"say \\\"cheese\\\"\" \"a\\\\b\"", IResult::Done("", "say \"cheese\" a\\b")),
+            ("\"line\\none\\ttwo\"", IResult::Done("", "line\none\ttwo")),
+            ("apple\nbanana", IResult::Done("banana", "apple")),
+            ("!!!", IResult::Error(ErrorKind::AlphaNumeric)),
+            ("\"bad \\q\"", IResult::Error(ErrorKind::AlphaNumeric)),
         ];
 
         for (input, expected) in inputs_and_expected {
             let produced = Parsers::phrase(input);
+            let expected = expected.map(str::to_string);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_quoted_string() {
+        let inputs_and_expected = vec![
+            ("\"\"", IResult::Done("", "")),
+            ("\"a | b\" rest", IResult::Done(" rest", "a | b")),
+            ("\"unterminated", IResult::Incomplete(Needed::Unknown)),
+            ("\"dangling \\", IResult::Incomplete(Needed::Unknown)),
+            ("plain", IResult::Error(ErrorKind::Char)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::quoted_string(input);
+            let expected = expected.map(str::to_string);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_amount() {
+        let inputs_and_expected = vec![
+            ("2", IResult::Done("", Ratio::from_integer(2))),
+            ("0.75", IResult::Done("", Ratio::new(3, 4))),
+            ("3/4", IResult::Done("", Ratio::new(3, 4))),
+            ("1 1/2", IResult::Done("", Ratio::new(3, 2))),
+            ("½", IResult::Done("", Ratio::new(1, 2))),
+            ("1½", IResult::Done("", Ratio::new(3, 2))),
+            ("2 ¾", IResult::Done("", Ratio::new(11, 4))),
+            ("2 cups", IResult::Done(" cups", Ratio::from_integer(2))),
+            ("1.5 g", IResult::Done(" g", Ratio::new(3, 2))),
+            ("1 1/2 cups", IResult::Done(" cups", Ratio::new(3, 2))),
+            ("cups", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::amount(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_magnitude() {
+        let inputs_and_expected = vec![
+            ("2", IResult::Done("", Magnitude::Exact(Ratio::from_integer(2)))),
+            ("2-3", IResult::Done("", Magnitude::Range(Ratio::from_integer(2), Ratio::from_integer(3)))),
+            ("2 - 3", IResult::Done("", Magnitude::Range(Ratio::from_integer(2), Ratio::from_integer(3)))),
+            ("2–3", IResult::Done("", Magnitude::Range(Ratio::from_integer(2), Ratio::from_integer(3)))),
+            ("½-1", IResult::Done("", Magnitude::Range(Ratio::new(1, 2), Ratio::from_integer(1)))),
+            ("1 1/2-2 cups", IResult::Done(" cups", Magnitude::Range(Ratio::new(3, 2), Ratio::from_integer(2)))),
+            ("3-2", IResult::Done("-2", Magnitude::Exact(Ratio::from_integer(3)))),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::magnitude(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_unit() {
+        let inputs_and_expected = vec![
+            ("g", IResult::Done("", Unit::Gram)),
+            ("cups of flour", IResult::Done(" of flour", Unit::Cup)),
+            ("fl oz", IResult::Done("", Unit::FluidOunce)),
+            ("imp fl oz", IResult::Done("", Unit::ImperialFluidOunce)),
+            ("°C", IResult::Done("", Unit::Celsius)),
+            ("cloves", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::unit(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_quantity() {
+        let inputs_and_expected = vec![
+            ("200 g", IResult::Done("", Quantity::whole(200, Unit::Gram))),
+            (" 200g ", IResult::Done("", Quantity::whole(200, Unit::Gram))),
+            ("1 1/2 cups", IResult::Done("", Quantity::new(Ratio::new(3, 2), Unit::Cup))),
+            ("½ tsp", IResult::Done("", Quantity::new(Ratio::new(1, 2), Unit::Teaspoon))),
+            ("0.5 l", IResult::Done("", Quantity::new(Ratio::new(1, 2), Unit::Liter))),
+            ("2-3 tbsp", IResult::Done("", Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Tablespoon))),
+            ("3", IResult::Done("", Quantity::whole(3, Unit::Each))),
+            ("2-3 cloves", IResult::Done("cloves", Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each))),
+            ("180 °C", IResult::Done("", Quantity::whole(180, Unit::Celsius))),
+            ("2 c", IResult::Done("c", Quantity::whole(2, Unit::Each))),
+            ("4 fl oz", IResult::Done("", Quantity::whole(4, Unit::FluidOunce))),
+            ("about 200 g", IResult::Done("", Quantity::whole(200, Unit::Gram).into_approximate())),
+            ("~ 2-3 cups", IResult::Done("", Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Cup).into_approximate())),
+            ("approx. 1 kg", IResult::Done("", Quantity::whole(1, Unit::Kilogram).into_approximate())),
+            ("some", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::quantity(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_measure_token() {
+        let inputs_and_expected = vec![
+            ("+measure: 2 cups", IResult::Done("", Token::Measure(Quantity::whole(2, Unit::Cup)))),
+            (" + measure : 1 1/2 tsp", IResult::Done("", Token::Measure(Quantity::new(Ratio::new(3, 2), Unit::Teaspoon)))),
+            ("+measure: 2-3", IResult::Done("", Token::Measure(Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each)))),
+            ("+measure: 200 g, sifted", IResult::Done(", sifted", Token::Measure(Quantity::whole(200, Unit::Gram)))),
+            ("+measure:", IResult::Error(ErrorKind::Alt)),
+            ("+measure: lots", IResult::Error(ErrorKind::Alt)),
+            ("measure: 2 cups", IResult::Error(ErrorKind::Char)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::measure_token(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_f_partition() {
+        let inputs_and_expected = vec![
+            ("+-", IResult::Done("", Portion::Fraction(1, 2))),
+            ("+--", IResult::Done("", Portion::Fraction(1, 3))),
+            ("++-", IResult::Done("", Portion::Fraction(2, 3))),
+            ("++--", IResult::Done("", Portion::Fraction(1, 2))),
+            ("+--- sauce", IResult::Done(" sauce", Portion::Fraction(1, 4))),
+            ("+", IResult::Error(ErrorKind::Complete)),
+            ("--", IResult::Error(ErrorKind::Many1)),
+            ("-+", IResult::Error(ErrorKind::Many1)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::f_partition(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_portion() {
+        let inputs_and_expected = vec![
+            ("+--", IResult::Done("", Portion::Fraction(1, 3))),
+            (" 1/3 ", IResult::Done("", Portion::Fraction(1, 3))),
+            ("2/4", IResult::Done("", Portion::Fraction(1, 2))),
+            ("1/3 cup", IResult::Done("", Portion::Quantity(Quantity::new(Ratio::new(1, 3), Unit::Cup)))),
+            ("4/3 cups", IResult::Done("", Portion::Quantity(Quantity::new(Ratio::new(4, 3), Unit::Cup)))),
+            ("4/3", IResult::Error(ErrorKind::MapRes)),
+            ("3/3", IResult::Error(ErrorKind::MapRes)),
+            ("0/3", IResult::Error(ErrorKind::MapRes)),
+            ("200 g", IResult::Done("", Portion::Quantity(Quantity::whole(200, Unit::Gram)))),
+            ("3/2 cups", IResult::Done("", Portion::Quantity(Quantity::new(Ratio::new(3, 2), Unit::Cup)))),
+            ("half", IResult::Done("", Portion::Pseudo("half".to_string()))),
+            ("!!", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::portion(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_take_token() {
+        let inputs_and_expected = vec![
+            ("+take: +--", IResult::Done("", Token::Take(Portion::Fraction(1, 3)))),
+            (" + take : 1/4", IResult::Done("", Token::Take(Portion::Fraction(1, 4)))),
+            ("+take: 1/3 cup", IResult::Done("", Token::Take(Portion::Quantity(Quantity::new(Ratio::new(1, 3), Unit::Cup))))),
+            ("+take: 4/3", IResult::Error(ErrorKind::MapRes)),
+            ("+take: 3/3", IResult::Error(ErrorKind::MapRes)),
+            ("+take: 0/3", IResult::Error(ErrorKind::MapRes)),
+            ("+take: 200 g", IResult::Done("", Token::Take(Portion::Quantity(Quantity::whole(200, Unit::Gram))))),
+            ("+leave: +--", IResult::Error(ErrorKind::Tag)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::take_token(input);
+            assert_eq!(expected, produced);
+        }
+
+        for input in &["* x +take: 4/3", "* x +take: 3/3", "* x +take: 0/3"] {
+            assert!(Parsers::parse_recipe(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_leave_token() {
+        let inputs_and_expected = vec![
+            ("+leave: ++-", IResult::Done("", Token::Leave(Portion::Fraction(2, 3)))),
+            ("+leave: 1/2", IResult::Done("", Token::Leave(Portion::Fraction(1, 2)))),
+            ("+take: 1/2", IResult::Error(ErrorKind::Tag)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::leave_token(input);
             assert_eq!(expected, produced);
         }
     }
@@ -381,6 +1234,8 @@ mod tests {
             ("* apple !!!!", IResult::Done("!!!!", Token::Ingredient("apple".to_string()))),
             ("* APPLE !!!!", IResult::Done("!!!!", Token::Ingredient("APPLE".to_string()))),
             ("* APPLE   007 !!!!", IResult::Done("!!!!", Token::Ingredient("APPLE   007".to_string()))),
+            ("* crème fraîche, cold", IResult::Done(", cold", Token::Ingredient("crème fraîche".to_string()))),
+            ("* \"salt; kosher\"", IResult::Done("", Token::Ingredient("salt; kosher".to_string()))),
         ];
 
         for (input, expected) in inputs_and_expected {
@@ -477,6 +1332,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_concrete_keyword_tokens() {
+        let inputs_and_expected = vec![
+            ("*tool: whisk", IResult::Done("", Token::Tool("whisk".to_string()))),
+            (" * tool : wooden spoon", IResult::Done("", Token::Tool("wooden spoon".to_string()))),
+            ("*container: mixing bowl, large", IResult::Done(", large", Token::Container("mixing bowl".to_string()))),
+            ("*appliance: oven", IResult::Done("", Token::Appliance("oven".to_string()))),
+            ("*tool whisk", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = alt!(input,
+                complete!(call!(Parsers::tool_token))
+                | complete!(call!(Parsers::container_token))
+                | complete!(call!(Parsers::appliance_token))
+            );
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_operator_keyword_tokens() {
+        let inputs_and_expected = vec![
+            ("+transfer: pour", IResult::Done("", Token::Transfer("pour".to_string()))),
+            ("+configure: 350 F", IResult::Done("", Token::Configure("350 F".to_string()))),
+            ("+meld: fold in", IResult::Done("", Token::Meld("fold in".to_string()))),
+            ("+place", IResult::Done("", Token::Place)),
+            ("+ remove *apple", IResult::Done("*apple", Token::Remove)),
+            ("+discard", IResult::Done("", Token::Discard)),
+            ("+empty", IResult::Done("", Token::Empty)),
+            ("+placement", IResult::Error(ErrorKind::Alt)),
+            ("+meld", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = alt!(input,
+                complete!(call!(Parsers::transfer_token))
+                | complete!(call!(Parsers::place_token))
+                | complete!(call!(Parsers::remove_token))
+                | complete!(call!(Parsers::configure_token))
+                | complete!(call!(Parsers::meld_token))
+                | complete!(call!(Parsers::discard_token))
+                | complete!(call!(Parsers::empty_token))
+            );
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_metadata_tokens() {
+        let inputs_and_expected = vec![
+            ("&set: batter", IResult::Done("", Token::TagSet("batter".to_string()))),
+            ("& get : batter", IResult::Done("", Token::TagGet("batter".to_string()))),
+            ("&set batter", IResult::Error(ErrorKind::Alt)),
+            ("&put: batter", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = alt!(input,
+                complete!(call!(Parsers::tag_set_token))
+                | complete!(call!(Parsers::tag_get_token))
+            );
+            assert_eq!(expected, produced);
+        }
+    }
+
     #[test]
     fn test_token() {
         let inputs_and_expected = vec![
@@ -485,6 +1406,22 @@ mod tests {
             ("/ mix", IResult::Done("", Token::Combine("mix".to_string()))),
             (", red", IResult::Done("", Token::Modifier("red".to_string()))),
             ("; gently", IResult::Done("", Token::Annotation("gently".to_string()))),
+            ("+measure: 2 cups", IResult::Done("", Token::Measure(Quantity::whole(2, Unit::Cup)))),
+            ("+take: +--", IResult::Done("", Token::Take(Portion::Fraction(1, 3)))),
+            ("+leave: 1/2", IResult::Done("", Token::Leave(Portion::Fraction(1, 2)))),
+            ("*tool: whisk", IResult::Done("", Token::Tool("whisk".to_string()))),
+            ("*container: bowl", IResult::Done("", Token::Container("bowl".to_string()))),
+            ("*appliance: oven", IResult::Done("", Token::Appliance("oven".to_string()))),
+            ("*tool", IResult::Done("", Token::Ingredient("tool".to_string()))),
+            ("+transfer: pour", IResult::Done("", Token::Transfer("pour".to_string()))),
+            ("+place", IResult::Done("", Token::Place)),
+            ("+remove", IResult::Done("", Token::Remove)),
+            ("+configure: high", IResult::Done("", Token::Configure("high".to_string()))),
+            ("+meld: fold", IResult::Done("", Token::Meld("fold".to_string()))),
+            ("+discard", IResult::Done("", Token::Discard)),
+            ("+empty", IResult::Done("", Token::Empty)),
+            ("&set: dough", IResult::Done("", Token::TagSet("dough".to_string()))),
+            ("&get: dough", IResult::Done("", Token::TagGet("dough".to_string()))),
         ];
 
         for (input, expected) in inputs_and_expected {
@@ -530,6 +1467,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gate_with() {
+        let mut names = VariantNames::new();
+        names.declare("vegan").expect("Unable to declare");
+        names.declare("gluten-free").expect("Unable to declare");
+
+        let inputs_and_expected = vec![
+            ("#vegan", IResult::Done("", allow![0])),
+            ("#!gluten-free, 3", IResult::Done("", block![1, 3])),
+            ("#2, vegan * tofu", IResult::Done("* tofu", allow![0, 2])),
+            ("#keto", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::gate_with(input, &names);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_condition_gate_with() {
+        let mut header = Header::new();
+        header.set_field("axis", "diet: vegan, omnivore").expect("Unable to declare");
+        header.set_field("axis", "method: grill, oven").expect("Unable to declare");
+
+        let diet = |g: Gate| Condition::on("diet", g);
+        let method = |g: Gate| Condition::on("method", g);
+
+        let inputs_and_expected = vec![
+            ("#diet: vegan", IResult::Done("", diet(allow![0]))),
+            ("# method: !oven, 3 * tofu", IResult::Done("* tofu", method(block![1, 3]))),
+            ("#diet: vegan and method: grill", IResult::Done("", Condition::All(vec![diet(allow![0]), method(allow![0])]))),
+            (
+                "#diet: omnivore or method: oven and not diet: vegan",
+                IResult::Done("", Condition::Any(vec![
+                    diet(allow![1]),
+                    Condition::All(vec![method(allow![1]), Condition::negate(diet(allow![0]))]),
+                ])),
+            ),
+            (
+                "#(diet: omnivore or method: oven) and not not diet: vegan",
+                IResult::Done("", Condition::All(vec![
+                    Condition::Any(vec![diet(allow![1]), method(allow![1])]),
+                    Condition::negate(Condition::negate(diet(allow![0]))),
+                ])),
+            ),
+            ("#diet: vegan oregano", IResult::Done("oregano", diet(allow![0]))),
+            ("#diet: keto", IResult::Error(ErrorKind::Alt)),
+            ("#spice: hot", IResult::Error(ErrorKind::Alt)),
+            ("#vegan", IResult::Error(ErrorKind::Complete)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::condition_gate_with(input, &header);
+            assert_eq!(expected, produced);
+        }
+
+        // A split gated by a condition on axes does not need a slot.
+        let produced = Parsers::split_with("* bacon #diet: omnivore", &header);
+        let expected = Split::conditional(flow![FlowItem::Token(Token::Ingredient("bacon".to_string()))], diet(allow![1]));
+        assert_eq!(IResult::Done("", expected), produced);
+
+        assert!(Parsers::split_set_with("[ * bacon #diet: omnivore | * tofu #0 ]", &header).is_err());
+        assert!(Parsers::split_set_with("[ * bacon #diet: omnivore | * tofu ]", &header).is_done());
+    }
+
     // #[test]
     // fn test_flow_item() {
     //     let inputs_and_expected = vec![
@@ -554,4 +1557,114 @@ mod tests {
     //         assert_eq!(expected, produced);
     //     }
     // }
+
+    #[test]
+    fn test_comment() {
+        let inputs_and_expected = vec![
+            ("% use tart apples", IResult::Done("", Comment::Line("use tart apples".to_string()))),
+            ("  %use tart apples  \r\n* apple", IResult::Done("* apple", Comment::Line("use tart apples".to_string()))),
+            ("%", IResult::Done("", Comment::Line("".to_string()))),
+            ("%{ peel\n  first }% * apple", IResult::Done("* apple", Comment::Block(" peel\n  first ".to_string()))),
+            ("%{}%", IResult::Done("", Comment::Block("".to_string()))),
+            ("%{ unterminated", IResult::Error(ErrorKind::Alt)),
+            ("* apple", IResult::Error(ErrorKind::Alt)),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::comment(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_flow() {
+        let apple = || FlowItem::Token(Token::Ingredient("apple".to_string()));
+        let slice = || FlowItem::Token(Token::Verb("slice".to_string()));
+
+        let inputs_and_expected = vec![
+            ("* apple\n= slice", IResult::Done("", Flow::new(vec![apple(), slice()]))),
+            ("\n\n  * apple\r\n\r\n\t= slice\n\n", IResult::Done("", Flow::new(vec![apple(), slice()]))),
+            ("* apple % the red kind\n= slice", IResult::Done("", Flow::new(vec![
+                apple(),
+                FlowItem::Comment(Comment::Line("the red kind".to_string())),
+                slice(),
+            ]))),
+            ("%{ prep }%\n* apple\n%{\n  thin\n}%= slice", IResult::Done("", Flow::new(vec![
+                FlowItem::Comment(Comment::Block(" prep ".to_string())),
+                apple(),
+                FlowItem::Comment(Comment::Block("\n  thin\n".to_string())),
+                slice(),
+            ]))),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::flow(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let inputs_and_expected = vec![
+            ("", Some(Header::new())),
+            ("@title: Pancakes\n@tags: breakfast, sweet\n* flour", Some(Header {
+                title: Some("Pancakes".to_string()),
+                tags: vec!["breakfast".to_string(), "sweet".to_string()],
+                ..Header::new()
+            })),
+            ("\n  @author : Mark  \n", Some(Header { author: Some("Mark".to_string()), ..Header::new() })),
+            ("@title: Pancakes\n@title: Waffles", None),
+            ("@calories: 500", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::header(input).to_result().ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_notes() {
+        let inputs_and_expected = vec![
+            ("", IResult::Done("", vec![])),
+            ("\n---\nServe warm.\nKeeps for\n  a day.\n\n\nFreezes well.\n", IResult::Done("", vec![
+                "Serve warm. Keeps for a day.".to_string(),
+                "Freezes well.".to_string(),
+            ])),
+            ("* apple", IResult::Done("* apple", vec![])),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::notes(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_recipe() {
+        let input = "@title: Apple Slices\n@servings: 2-3\n\n* apple\n= slice\n\n---\nUse tart apples.\n";
+        let recipe = Parsers::recipe(input).to_result().expect("Unable to parse recipe");
+
+        assert_eq!(&Some("Apple Slices".to_string()), &recipe.header().title);
+        assert_eq!(&Flow::new(vec![
+            FlowItem::Token(Token::Ingredient("apple".to_string())),
+            FlowItem::Token(Token::Verb("slice".to_string())),
+        ]), recipe.flow());
+        assert_eq!(&["Use tart apples.".to_string()], recipe.notes());
+
+        let inputs_and_expected = vec![
+            ("", true),
+            ("* apple", true),
+            ("@title: Apple\n* apple ]", false),
+            ("* apple\n---", true),
+            ("* apple !!!", false),
+            ("@calories: 500\n* apple", false),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Parsers::recipe(input).is_done();
+            assert_eq!(expected, produced);
+        }
+    }
 }
+
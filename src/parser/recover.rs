@@ -0,0 +1,332 @@
+use nom::IResult;
+
+use parallel::flow::{Flow, FlowItem, Split, SplitSet};
+use recipe::{Header, Recipe};
+use super::*;
+use super::error::{offset, diagnose_header_field, diagnose_token, is_misread_keyword};
+
+// Characters that may begin a new token or split component, at which parsing resumes after an error.
+const RESYNC_CHARS: &[char] = &[
+    INGREDIENT_SIGIL, ACTION_SIGIL, COMBINATION_SIGIL, MODIFIER_SIGIL, ANNOTATION_SIGIL,
+    CONCRETE_TOKEN_SIGIL, OPERATOR_TOKEN_SIGIL, METADATA_TOKEN_SIGIL,
+    VAR_SPLIT_START_SIGIL, VAR_SPLIT_SEP_SIGIL, VAR_SPLIT_CLOSE_SIGIL, VAR_SPLIT_TAG_SIGIL,
+    LINE_COMMENT_SIGIL,
+];
+
+const SPLIT_DELIMITER_CHARS: &[char] = &[VAR_SPLIT_SEP_SIGIL, VAR_SPLIT_CLOSE_SIGIL];
+
+// Characters at which a flow nested in a split ends.
+const SPLIT_BOUNDARY_CHARS: &[char] = &[VAR_SPLIT_SEP_SIGIL, VAR_SPLIT_CLOSE_SIGIL, VAR_SPLIT_TAG_SIGIL];
+
+/// Skips past the start of a malformed item, up to the next character in `chars` or notes separator line.
+fn resync<'a>(rest: &'a str, chars: &[char]) -> &'a str {
+    let skip = rest.chars().next().map_or(0, char::len_utf8);
+    let notes = format!("\n{}", NOTES_SEPARATOR);
+
+    let pos = rest[skip..].find(chars).into_iter()
+        .chain(rest[skip..].find(&notes as &str).map(|p| p + 1))
+        .min();
+
+    match pos {
+        Some(pos) => &rest[skip + pos..],
+        None => &rest[rest.len()..],
+    }
+}
+
+/// Parses recipe source while collecting diagnostics, skipping over malformed items instead of stopping at them.
+pub struct Recovery<'s> {
+    source: &'s str,
+    diagnostics: Vec<ParseError>,
+    // The header, whose declared variant names and axes gates may refer to.
+    header: Header,
+}
+
+impl<'s> Recovery<'s> {
+    pub fn new(source: &'s str) -> Self {
+        Recovery { source, diagnostics: vec![], header: Header::new() }
+    }
+
+    pub fn recipe(mut self) -> (Recipe<'s>, Vec<ParseError>) {
+        let (header, rest) = self.header(self.source);
+        self.header = header.clone();
+        let (flow, rest) = self.flow(rest, false);
+
+        let notes = match Parsers::notes(rest) {
+            IResult::Done(_, notes) => notes,
+            _ => vec![],
+        };
+
+        (Recipe::new(header, flow, notes), self.diagnostics)
+    }
+
+    pub fn flow_only(mut self) -> (Flow<'s>, Vec<ParseError>) {
+        let mut items = vec![];
+        let mut rest = self.source;
+
+        // Outside of a recipe, a notes separator is just another malformed item.
+        loop {
+            let (flow, next) = self.flow(rest, false);
+            items.extend(flow);
+
+            if next.is_empty() {
+                return (Flow::new(items), self.diagnostics);
+            }
+
+            self.diagnostics.push(diagnose_token(self.source, next, false));
+            rest = resync(next, RESYNC_CHARS);
+        }
+    }
+
+    fn header(&mut self, source: &'s str) -> (Header, &'s str) {
+        let mut header = Header::new();
+        let mut rest = source;
+
+        loop {
+            let trimmed = rest.trim_start();
+
+            if !trimmed.starts_with(HEADER_FIELD_SIGIL) {
+                return (header, rest);
+            }
+
+            let key_offset = offset(self.source, trimmed) + HEADER_FIELD_SIGIL.len_utf8();
+
+            // Malformed header lines are skipped in their entirety.
+            rest = match Parsers::header_field(rest) {
+                IResult::Done(next, (key, value)) => {
+                    if let Err(error) = header.set_field(key, value) {
+                        self.diagnostics.push(diagnose_header_field(self.source, key_offset, error));
+                    }
+                    next
+                },
+                _ => {
+                    self.diagnostics.push(ParseError::at(self.source, key_offset, "expected a header field of the form '@name: value'"));
+                    &trimmed[trimmed.find('\n').unwrap_or(trimmed.len())..]
+                },
+            };
+        }
+    }
+
+    // Parses a flow one item at a time, stopping at anything that may legally follow the flow.
+    // Items are read by the regular grammar, and only those it rejects are recovered from.
+    fn flow(&mut self, rest: &'s str, nested: bool) -> (Flow<'s>, &'s str) {
+        let mut items = vec![];
+        let mut rest = rest;
+
+        loop {
+            let trimmed = rest.trim_start();
+
+            match trimmed.chars().next() {
+                None => return (Flow::new(items), trimmed),
+                Some(c) if nested && SPLIT_BOUNDARY_CHARS.contains(&c) => return (Flow::new(items), trimmed),
+                Some(_) if !nested && trimmed.starts_with(NOTES_SEPARATOR) => return (Flow::new(items), trimmed),
+                _ => {},
+            }
+
+            match Parsers::flow_item_with(trimmed, &self.header) {
+                IResult::Done(next, FlowItem::Token(ref token)) if is_misread_keyword(token, next) => {},
+                IResult::Done(next, item) => {
+                    items.push(item);
+                    rest = next;
+                    continue;
+                },
+                _ => {},
+            }
+
+            rest = match trimmed.chars().next() {
+                Some(VAR_SPLIT_START_SIGIL) => {
+                    let (split_set, next) = self.split_set(trimmed);
+                    items.push(FlowItem::Split(split_set));
+                    next
+                },
+                Some(_) if trimmed.starts_with(LINE_COMMENT_SIGIL) => {
+                    // Only an unterminated block comment can fail, and it runs to the end of the source.
+                    self.diagnostics.push(ParseError::at(self.source, self.source.len(),
+                        format!("expected '{}' to close the block comment", BLOCK_COMMENT_CLOSE)));
+                    &trimmed[trimmed.len()..]
+                },
+                _ => {
+                    self.diagnostics.push(diagnose_token(self.source, trimmed, nested));
+                    resync(trimmed, RESYNC_CHARS)
+                },
+            };
+        }
+    }
+
+    // Parses a split set one split at a time, recovering from any malformed splits.
+    fn split_set(&mut self, rest: &'s str) -> (SplitSet<'s>, &'s str) {
+        let start = offset(self.source, rest);
+        let mut splits = vec![];
+        let mut rest = &rest[VAR_SPLIT_START_SIGIL.len_utf8()..];
+
+        loop {
+            let (split, next) = self.split(rest);
+            splits.push(split);
+            rest = next;
+
+            match rest.chars().next() {
+                Some(VAR_SPLIT_SEP_SIGIL) => {
+                    rest = &rest[VAR_SPLIT_SEP_SIGIL.len_utf8()..];
+                },
+                Some(VAR_SPLIT_CLOSE_SIGIL) => {
+                    // A split set that mixes both kinds of gate keeps all of its splits, as if the slot gates were absent.
+                    if Parsers::checked_split_set(splits.clone()).is_err() {
+                        self.diagnostics.push(ParseError::at(self.source, start,
+                            "expected the gates of a split to either all give slots, or all give conditions on axes"));
+                    }
+
+                    return (SplitSet::new(splits), &rest[VAR_SPLIT_CLOSE_SIGIL.len_utf8()..]);
+                },
+                Some(_) => {
+                    // Treat the stray item as the start of another split.
+                    self.diagnostics.push(ParseError::at(self.source, offset(self.source, rest), "expected '|' or ']' after the gate"));
+                },
+                None => {
+                    let opened = ParseError::at(self.source, start, "");
+                    self.diagnostics.push(ParseError::at(self.source, self.source.len(),
+                        format!("expected ']' to close the split opened at line {}, column {}", opened.line(), opened.column())));
+                    return (SplitSet::new(splits), rest);
+                },
+            }
+        }
+    }
+
+    // Parses a single split, up to the next split delimiter. A malformed gate is skipped, leaving the split ungated.
+    fn split(&mut self, rest: &'s str) -> (Split<'s>, &'s str) {
+        if let IResult::Done(next, split) = Parsers::split_with(rest, &self.header) {
+            let next = next.trim_start();
+
+            if next.starts_with(SPLIT_DELIMITER_CHARS) {
+                return (split, next);
+            }
+        }
+
+        let (flow, rest) = self.flow(rest, true);
+
+        if !rest.starts_with(VAR_SPLIT_TAG_SIGIL) {
+            return (Split::new(flow, block!()), rest);
+        }
+
+        // With the flow already read, what remains of the split is just its gate.
+        match Parsers::split_with(rest, &self.header) {
+            IResult::Done(next, gated) if next.len() < rest.len() => {
+                let split = match gated.condition() {
+                    Some(condition) => Split::conditional(flow, condition.clone()),
+                    None => Split::new(flow, gated.gate().clone()),
+                };
+
+                (split, next.trim_start())
+            },
+            _ => {
+                let gate_offset = offset(self.source, rest) + VAR_SPLIT_TAG_SIGIL.len_utf8();
+                self.diagnostics.push(ParseError::at(self.source, gate_offset,
+                    "expected a comma-separated list of slot numbers or declared variant names after '#', such as '#0, 2', \
+                    or a condition on declared axes, such as '#diet: vegan and not method: grill'"));
+
+                (Split::new(flow, block!()), resync(rest, SPLIT_DELIMITER_CHARS))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resync;
+    use super::super::Parsers;
+
+    use parallel::axis::Condition;
+    use parallel::flow::{Comment, Flow, FlowItem, Split, SplitSet};
+    use parallel::gate::Gate;
+    use token::Token;
+
+    #[test]
+    fn test_resync() {
+        let inputs_and_expected = vec![
+            ("* !!! = slice", "= slice"),
+            ("+measure: lots ] * apple", "] * apple"),
+            ("!!!", ""),
+            ("", ""),
+            ("ü [ * apple ]", "[ * apple ]"),
+            ("* !!\n---\nServe warm.", "---\nServe warm."),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = resync(input, super::RESYNC_CHARS);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_parse_flow_recovering() {
+        let apple = || FlowItem::Token(Token::Ingredient("apple".to_string()));
+        let slice = || FlowItem::Token(Token::Verb("slice".to_string()));
+
+        let inputs_and_expected = vec![
+            ("* apple = slice", (Flow::new(vec![apple(), slice()]), vec![])),
+            ("* apple !!! = slice", (Flow::new(vec![apple(), slice()]), vec![(1, 9)])),
+            ("* !! * apple\n+measure: lots\n= slice ]", (Flow::new(vec![apple(), slice()]), vec![(1, 3), (2, 11), (3, 9)])),
+            ("* apple\n---\n= slice", (Flow::new(vec![apple(), slice()]), vec![(2, 1)])),
+            (
+                "[ * apple #x | = !! ] = slice",
+                (
+                    Flow::new(vec![
+                        FlowItem::Split(splitset![
+                            Split::new(Flow::new(vec![apple()]), block![]),
+                            Split::new(Flow::new(vec![]), block![]),
+                        ]),
+                        slice(),
+                    ]),
+                    vec![(1, 12), (1, 18)],
+                ),
+            ),
+            ("* apple % fresh\n= slice %{ unterminated", (Flow::new(vec![
+                apple(),
+                FlowItem::Comment(Comment::Line("fresh".to_string())),
+                slice(),
+            ]), vec![(2, 24)])),
+            (
+                "[ * apple",
+                (Flow::new(vec![FlowItem::Split(splitset![Split::new(Flow::new(vec![apple()]), block![])])]), vec![(1, 10)]),
+            ),
+        ];
+
+        for (input, (expected_flow, expected_positions)) in inputs_and_expected {
+            let (produced_flow, diagnostics) = Parsers::parse_flow_recovering(input);
+            let produced_positions: Vec<_> = diagnostics.iter().map(|d| (d.line(), d.column())).collect();
+            assert_eq!((expected_flow, expected_positions), (produced_flow, produced_positions));
+        }
+    }
+
+    #[test]
+    fn test_parse_recipe_recovering() {
+        let input = "@title: Pie\n@calories: 500\n@title: Tart\n@servings: 4\n* apple\n* !!\n---\nServe warm.";
+        let (recipe, diagnostics) = Parsers::parse_recipe_recovering(input);
+
+        assert_eq!(&Some("Pie".to_string()), &recipe.header().title);
+        assert!(recipe.header().servings.is_some());
+        assert_eq!(&Flow::new(vec![FlowItem::Token(Token::Ingredient("apple".to_string()))]), recipe.flow());
+        assert_eq!(&["Serve warm.".to_string()], recipe.notes());
+
+        let produced: Vec<_> = diagnostics.iter().map(|d| (d.line(), d.column())).collect();
+        assert_eq!(vec![(2, 2), (3, 2), (6, 3)], produced);
+    }
+
+    #[test]
+    fn test_parse_mixed_split_recovering() {
+        let input = "@axis: diet: vegan, omnivore\n[ * bacon #diet: omnivore | * tofu #0 ] = fry";
+        let (recipe, diagnostics) = Parsers::parse_recipe_recovering(input);
+
+        let bacon = FlowItem::Token(Token::Ingredient("bacon".to_string()));
+        let tofu = FlowItem::Token(Token::Ingredient("tofu".to_string()));
+        let expected = Flow::new(vec![
+            FlowItem::Split(splitset![
+                Split::conditional(Flow::new(vec![bacon]), Condition::on("diet", allow![1])),
+                Split::new(Flow::new(vec![tofu]), allow![0]),
+            ]),
+            FlowItem::Token(Token::Verb("fry".to_string())),
+        ]);
+        assert_eq!(&expected, recipe.flow());
+
+        let produced: Vec<_> = diagnostics.iter().map(|d| (d.line(), d.column())).collect();
+        assert_eq!(vec![(2, 1)], produced);
+    }
+}
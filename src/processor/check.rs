@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+
+use failure::Error;
+
+use parallel::axis::Assignment;
+use parallel::flow::Flow;
+use parallel::gate::Slot;
+use token::Token;
+use super::meta::{MetaError, process_meta};
+use super::signature::{Signature, SignatureError};
+use super::tag::{TagError, TagUse, check_tags};
+
+pub use super::signature::Kind;
+
+/// A problem with a single variant of a flow.
+/// Each error carries the slot stack and assignment selecting the variant, in the form accepted by `Flow::iter_walks_with`,
+/// and positions are indices into the walk for that variant.
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum CheckError {
+    #[fail(display = "stack underflow in variant {:?} {:?} at position {}; {:?} needs {} item(s), found {}", slot_stack, assignment, index, token, needed, found)]
+    Underflow {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        index: usize,
+        token: Box<Token>,
+        needed: usize,
+        found: usize,
+    },
+
+    #[fail(display = "type mismatch in variant {:?} {:?} at position {}; {:?} expected {}, found {}", slot_stack, assignment, index, token, expected, found)]
+    TypeMismatch {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        index: usize,
+        token: Box<Token>,
+        expected: Kind,
+        found: Kind,
+    },
+
+    #[fail(display = "token in variant {:?} {:?} at position {} has no preceding element to attach to: {:?}", slot_stack, assignment, index, token)]
+    Orphan {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        index: usize,
+        token: Box<Token>,
+    },
+
+    #[fail(display = "misused tag in variant {:?} {:?}: {}", slot_stack, assignment, error)]
+    Tag {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        error: TagError,
+    },
+
+    #[fail(display = "nothing remains at the end of variant {:?} {:?}", slot_stack, assignment)]
+    Empty {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+    },
+
+    #[fail(display = "expected a single item at the end of variant {:?} {:?}; found: {:?}", slot_stack, assignment, leftover)]
+    Leftover {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        leftover: Vec<Kind>,
+    },
+
+    #[fail(display = "expected a dish at the end of variant {:?} {:?}; found {}", slot_stack, assignment, found)]
+    NotADish {
+        slot_stack: Vec<Slot>,
+        assignment: Assignment,
+        found: Kind,
+    },
+}
+
+/// Verifies the stack effects of every variant of a flow, without evaluating any of them.
+pub struct Checker;
+
+impl Checker {
+    /// Checks every variant of a flow, stopping at the first error.
+    pub fn check(flow: &Flow) -> Result<(), Error> {
+        match Checker::check_all(flow).into_iter().next() {
+            Some(error) => Err(error)?,
+            None => Ok(()),
+        }
+    }
+
+    /// Checks every variant of a flow, reporting the first error found in each.
+    pub fn check_all(flow: &Flow) -> Vec<CheckError> {
+        flow.variants()
+            .filter_map(|variant| Checker::check_walk(&variant.walk, &variant.slot_stack, &variant.assignment).err())
+            .collect()
+    }
+
+    /// Checks a single walk through a flow, as selected by a slot stack and assignment.
+    pub fn check_walk(walk: &[&Token], slot_stack: &[Slot], assignment: &Assignment) -> Result<Kind, CheckError> {
+        let (_, orphans) = process_meta(walk.iter().map(|&t| t.clone()));
+
+        if let Some(orphan) = orphans.into_iter().next() {
+            let index = match orphan {
+                MetaError::OrphanModifier{index, ..} | MetaError::OrphanAnnotation{index, ..} => index,
+            };
+
+            return Err(CheckError::Orphan{slot_stack: slot_stack.to_vec(), assignment: assignment.clone(), index, token: Box::new(walk[index].clone())});
+        }
+
+        if let Some(error) = check_tags(walk.iter().map(|&t| TagUse::of_token(t))).into_iter().next() {
+            return Err(CheckError::Tag{slot_stack: slot_stack.to_vec(), assignment: assignment.clone(), error});
+        }
+
+        let mut stack = vec![];
+        let mut tags = BTreeMap::new();
+
+        for (index, &token) in walk.iter().enumerate() {
+            let op = Op { slot_stack, assignment, index, token };
+            op.apply(&mut stack, &mut tags)?;
+        }
+
+        match stack.len() {
+            0 => Err(CheckError::Empty{slot_stack: slot_stack.to_vec(), assignment: assignment.clone()}),
+            1 if stack[0].is_dish() => Ok(stack[0]),
+            1 => Err(CheckError::NotADish{slot_stack: slot_stack.to_vec(), assignment: assignment.clone(), found: stack[0]}),
+            _ => Err(CheckError::Leftover{slot_stack: slot_stack.to_vec(), assignment: assignment.clone(), leftover: stack}),
+        }
+    }
+}
+
+// The token currently being checked, used to describe errors.
+struct Op<'a> {
+    slot_stack: &'a [Slot],
+    assignment: &'a Assignment,
+    index: usize,
+    token: &'a Token,
+}
+
+impl<'a> Op<'a> {
+    fn apply(&self, stack: &mut Vec<Kind>, tags: &mut BTreeMap<String, Kind>) -> Result<(), CheckError> {
+        let signature = match Signature::of_token(self.token) {
+            Some(signature) => signature,
+            None => return Ok(()),
+        };
+
+        let top = stack.last().cloned();
+        signature.apply(stack).map_err(|error| self.error(error))?;
+
+        match *self.token {
+            Token::TagSet(ref tag) => {
+                tags.insert(tag.clone(), top.expect("a tag is only set on a non-empty stack"));
+            },
+            Token::TagGet(ref tag) => {
+                // Tags have already been checked, so the tag is always present.
+                if let Some(kind) = tags.remove(tag) {
+                    stack.push(kind);
+                }
+            },
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, error: SignatureError) -> CheckError {
+        let slot_stack = self.slot_stack.to_vec();
+        let assignment = self.assignment.clone();
+        let index = self.index;
+        let token = Box::new(self.token.clone());
+
+        match error {
+            SignatureError::Underflow{needed, found} => CheckError::Underflow{slot_stack, assignment, index, token, needed, found},
+            SignatureError::Mismatch{expected, found, ..} => CheckError::TypeMismatch{slot_stack, assignment, index, token, expected, found},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checker, CheckError, Kind};
+    use super::super::Operatable;
+    use super::super::eval::Evaluator;
+    use super::super::tag::TagError;
+
+    use parser::Parsers;
+    use token::Token;
+
+    #[test]
+    fn test_check() {
+        let inputs_and_expected = vec![
+            ("* apple", true),
+            ("* apple * pear / mix", true),
+            ("* apple *tool: knife = slice *container: bowl +place = toss", true),
+            ("*container: bowl * apple +place = toss +remove", false),
+            ("* apple *container: bowl +place *appliance: oven +configure: 200 degrees +transfer: tip &set: baking +discard &get: baking", true),
+            ("* apple *container: bowl +place +empty +configure: chilled", false),
+            ("* apple +configure: chilled", false),
+            ("* apple [ * pear / mix #1 ]", true),
+            ("* apple &set: fruit * pear = slice &get: fruit / mix", true),
+            ("* dough +take: 1/3 &set: reserved = roll &get: reserved / join", true),
+            ("* dough +leave: 1/3 = roll", false),
+            ("* apple [ / mix #1 ]", false),
+            ("* apple [ * pear #1 ]", false),
+            ("", false),
+            ("*container: bowl", false),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let recipe = Parsers::parse_recipe(input).expect("Unable to parse recipe");
+            let produced = Checker::check(recipe.flow()).is_ok();
+            assert_eq!(expected, produced, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_check_agrees_with_evaluation() {
+        let inputs = vec![
+            "* apple *tool: knife = slice *container: bowl +place = toss",
+            "*container: bowl * apple +place = toss +remove",
+            "* apple *container: bowl +place *appliance: oven +configure: 200 degrees +transfer: tip &set: baking +discard &get: baking",
+            "* apple *container: bowl +place +empty +configure: chilled",
+            "* dough +take: 1/3 &set: reserved = roll &get: reserved / join",
+            "* apple [ / mix #1 | * pear / mix #2 ]",
+            "*container: bowl",
+            "*tool: knife",
+        ];
+
+        for input in inputs {
+            let recipe = Parsers::parse_recipe(input).expect("Unable to parse recipe");
+
+            for variant in recipe.flow().variants() {
+                let checked = Checker::check_walk(&variant.walk, &variant.slot_stack, &variant.assignment).is_ok();
+                let evaluated = Operatable::create_operatable_stack(variant.walk.into_iter().cloned())
+                    .and_then(Evaluator::evaluate)
+                    .is_ok();
+                assert_eq!(checked, evaluated, "{} {:?}", input, variant.slot_stack);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_all() {
+        let s = |s: &str| s.to_string();
+
+        let inputs_and_expected = vec![
+            (
+                "* apple [ / mix #1 | * pear / mix #2 ]",
+                vec![CheckError::Underflow{slot_stack: vec![1], assignment: btreemap![], index: 1, token: Box::new(Token::Combine(s("mix"))), needed: 2, found: 1}],
+            ),
+            (
+                "* apple [ *container: bowl #0 ] [ = slice #0, 1 | * pear #3 ]",
+                vec![
+                    CheckError::Leftover{slot_stack: vec![3], assignment: btreemap![], leftover: vec![Kind::Mixture, Kind::Mixture]},
+                    CheckError::TypeMismatch{slot_stack: vec![0], assignment: btreemap![], index: 2, token: Box::new(Token::Verb(s("slice"))), expected: Kind::Mixture, found: Kind::Vessel},
+                ],
+            ),
+            (
+                "* apple *container: bowl +place * pear +meld: stir",
+                vec![CheckError::TypeMismatch{slot_stack: vec![], assignment: btreemap![], index: 4, token: Box::new(Token::Meld(s("stir"))), expected: Kind::System, found: Kind::Mixture}],
+            ),
+            (
+                "* apple *container: bowl +place * pear *container: pot +place +meld: stir",
+                vec![CheckError::Leftover{slot_stack: vec![], assignment: btreemap![], leftover: vec![Kind::Vessel, Kind::System]}],
+            ),
+            ("*container: bowl", vec![CheckError::NotADish{slot_stack: vec![], assignment: btreemap![], found: Kind::Vessel}]),
+            (
+                "* apple [ &set: fruit #1 ] * pear &get: fruit / mix",
+                vec![CheckError::Tag{slot_stack: vec![0], assignment: btreemap![], error: TagError::Unknown{index: 2, tag: s("fruit")}}],
+            ),
+            (
+                "@axis: diet: vegan, omnivore\n* apple [ / mix #diet: omnivore ]",
+                vec![CheckError::Underflow{
+                    slot_stack: vec![],
+                    assignment: btreemap![s("diet") => 1],
+                    index: 1,
+                    token: Box::new(Token::Combine(s("mix"))),
+                    needed: 2,
+                    found: 1,
+                }],
+            ),
+            ("+discard", vec![CheckError::Underflow{slot_stack: vec![], assignment: btreemap![], index: 0, token: Box::new(Token::Discard), needed: 1, found: 0}]),
+            ("+place , gently", vec![CheckError::Orphan{slot_stack: vec![], assignment: btreemap![], index: 1, token: Box::new(Token::Modifier(s("gently")))}]),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let recipe = Parsers::parse_recipe(input).expect("Unable to parse recipe");
+            let produced = Checker::check_all(recipe.flow());
+            assert_eq!(expected, produced);
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::BTreeMap;
+
+use failure::Error;
+
+use super::{Operatable, Concrete, Mixture, Part, Vessel, System};
+use super::signature::{Kind, Signature, SignatureError};
+use super::tag::{TagError, TagUse, check_tags};
+use super::vessel::{Registry, VesselError};
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum EvaluationError {
+    #[fail(display = "stack underflow at position {}; {:?} needs {} item(s), found {}", index, operatable, needed, found)]
+    Underflow {
+        index: usize,
+        operatable: Operatable,
+        needed: usize,
+        found: usize,
+    },
+
+    #[fail(display = "type mismatch at position {}; {:?} expected {}, found: {:?}", index, operatable, expected, found)]
+    TypeMismatch {
+        index: usize,
+        operatable: Operatable,
+        expected: &'static str,
+        found: Box<Concrete>,
+    },
+
+    #[fail(display = "nothing remains after evaluation")]
+    Empty,
+
+    #[fail(display = "expected a single item after evaluation; found: {:?}", leftover)]
+    Leftover {
+        leftover: Vec<Concrete>,
+    },
+
+    #[fail(display = "expected a dish after evaluation; found: {:?}", found)]
+    NotADish {
+        found: Box<Concrete>,
+    },
+}
+
+/// A single step of an evaluation, with the state of the stack after it was performed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TraceStep {
+    pub operatable: Operatable,
+    pub stack: Vec<Concrete>,
+}
+
+/// The outcome of a successful evaluation: the finished dish, how it was arrived at, and the vessels used along the way.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Evaluation {
+    pub result: Concrete,
+    pub trace: Vec<TraceStep>,
+    pub vessels: Registry,
+}
+
+/// A stack machine that reduces operatables into a single concrete result.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Evaluator {
+    stack: Vec<Concrete>,
+    trace: Vec<TraceStep>,
+    // Named intermediate products that have been set aside, along with where they were set.
+    tags: BTreeMap<String, (usize, Concrete)>,
+    vessels: Registry,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Evaluator::default()
+    }
+
+    /// Evaluates a sequence of operatables, such as those created from a single walk through a flow.
+    /// Tags are checked for consistency before anything is evaluated.
+    pub fn evaluate<II: IntoIterator<Item = Operatable>>(operatables: II) -> Result<Evaluation, Error> {
+        let operatables: Vec<_> = operatables.into_iter().collect();
+
+        if let Some(error) = check_tags(operatables.iter().map(TagUse::of_operatable)).into_iter().next() {
+            Err(error)?;
+        }
+
+        let mut evaluator = Evaluator::new();
+
+        for (index, operatable) in operatables.into_iter().enumerate() {
+            evaluator.step(index, operatable)?;
+        }
+
+        evaluator.finish()
+    }
+
+    pub fn stack(&self) -> &[Concrete] {
+        &self.stack
+    }
+
+    pub fn trace(&self) -> &[TraceStep] {
+        &self.trace
+    }
+
+    /// The item set aside under a tag, if it has not yet been used.
+    pub fn tagged(&self, tag: &str) -> Option<&Concrete> {
+        self.tags.get(tag).map(|(_, concrete)| concrete)
+    }
+
+    pub fn vessels(&self) -> &Registry {
+        &self.vessels
+    }
+
+    /// Performs a single operation, recording it in the trace.
+    /// The stack is checked against the signature of the operation before anything is changed.
+    pub fn step(&mut self, index: usize, operatable: Operatable) -> Result<(), Error> {
+        let op = Op { index, operatable: &operatable };
+        let signature = Signature::of_operatable(&operatable);
+
+        let mut kinds: Vec<_> = self.stack.iter().map(Kind::of).collect();
+        signature.apply(&mut kinds).map_err(|error| self.signature_error(&op, signature, error))?;
+
+        match operatable {
+            Operatable::Ingredient(ref i) => self.stack.push(Concrete::Ingredient(i.clone())),
+            Operatable::Tool(ref t) => self.stack.push(Concrete::Tool(t.clone())),
+            Operatable::Container(ref c) => self.stack.push(Concrete::Container(c.clone())),
+            Operatable::Appliance(ref a) => self.stack.push(Concrete::Appliance(a.clone())),
+            Operatable::Verb(ref verb) => {
+                // A verb may use a tool on top of the stack, and acts on the item below it.
+                let tool = match self.stack.last() {
+                    Some(&Concrete::Tool(_)) => match self.stack.pop() {
+                        Some(Concrete::Tool(tool)) => Some(tool),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+
+                let processed = |m: Mixture| Mixture::Processed(Box::new(m), verb.clone(), tool.clone());
+
+                let result = match self.pop(&op)? {
+                    Concrete::System(System(vessel, mixture)) => {
+                        let mixture = processed(mixture);
+                        self.vessels.refill(index, &vessel, &mixture);
+                        Concrete::System(System(vessel, mixture))
+                    },
+                    found => Concrete::Mixture(processed(op.expect_mixture(found)?)),
+                };
+
+                self.stack.push(result);
+            },
+            Operatable::Combine(_) => {
+                let b = self.pop(&op)?;
+                let a = self.pop(&op)?;
+
+                let compound = Mixture::Compound(Box::new(op.expect_mixture(a)?), Box::new(op.expect_mixture(b)?));
+                self.stack.push(Concrete::Mixture(compound));
+            },
+            Operatable::Measure(quantity) => {
+                let mixture = self.pop(&op).and_then(|c| op.expect_mixture(c))?;
+                self.stack.push(Concrete::Mixture(Mixture::Measured(Box::new(mixture), quantity)));
+            },
+            Operatable::Place => {
+                // The vessel and the mixture may be given in either order.
+                let b = self.pop(&op)?;
+                let a = self.pop(&op)?;
+
+                let system = match Op::is_vessel(&a) {
+                    true => System(op.expect_vessel(a)?, op.expect_mixture(b)?),
+                    false => System(op.expect_vessel(b)?, op.expect_mixture(a)?),
+                };
+
+                self.vessels.fill(index, &system.0, &system.1)?;
+                self.stack.push(Concrete::System(system));
+            },
+            Operatable::Remove => {
+                let System(vessel, mixture) = self.pop(&op).and_then(|c| op.expect_system(c))?;
+                self.vessels.empty(index, &vessel)?;
+
+                self.stack.push(Concrete::Vessel(vessel));
+                self.stack.push(Concrete::Mixture(mixture));
+            },
+            Operatable::Transfer(_) => {
+                // Moves the contents of a system into the vessel on top of the stack, leaving the original vessel empty.
+                let target = self.pop(&op).and_then(|c| op.expect_vessel(c))?;
+                let System(source, mixture) = self.pop(&op).and_then(|c| op.expect_system(c))?;
+                self.vessels.empty(index, &source)?;
+                self.vessels.fill(index, &target, &mixture)?;
+
+                self.stack.push(Concrete::Vessel(source));
+                self.stack.push(Concrete::System(System(target, mixture)));
+            },
+            Operatable::Take(ref portion) | Operatable::Leave(ref portion) => {
+                // Divides a mixture in two, with the part that is carried on with on top of the stack.
+                let mixture = self.pop(&op).and_then(|c| op.expect_mixture(c))?;
+                let (portion, rest) = Part::split(portion.clone());
+
+                let (kept, carried) = match operatable {
+                    Operatable::Take(_) => (rest, portion),
+                    _ => (portion, rest),
+                };
+
+                self.stack.push(Concrete::Mixture(Mixture::Portioned(Box::new(mixture.clone()), kept)));
+                self.stack.push(Concrete::Mixture(Mixture::Portioned(Box::new(mixture), carried)));
+            },
+            Operatable::TagSet(ref tag) => {
+                // Sets aside the item on top of the stack, to be brought back by name later on.
+                let item = self.pop(&op)?;
+
+                if let Some((set_index, _)) = self.tags.insert(tag.clone(), (index, item)) {
+                    Err(TagError::Unused{index: set_index, tag: tag.clone()})?;
+                }
+            },
+            Operatable::TagGet(ref tag) => {
+                let (_, item) = self.tags.remove(tag).ok_or_else(|| TagError::Unknown{index, tag: tag.clone()})?;
+                self.stack.push(item);
+            },
+            Operatable::Configure(ref setting) => {
+                // Sets up the vessel on top of the stack, whether or not it is holding anything.
+                let vessel = match self.stack.last() {
+                    Some(&Concrete::System(System(ref vessel, _))) => vessel.clone(),
+                    Some(top) => op.expect_vessel(top.clone())?,
+                    None => Err(op.underflow(1, 0))?,
+                };
+
+                self.vessels.configure(&vessel, setting);
+            },
+            Operatable::Empty => {
+                // Throws away the contents of a vessel, keeping the vessel.
+                let System(vessel, _) = self.pop(&op).and_then(|c| op.expect_system(c))?;
+                self.vessels.empty(index, &vessel)?;
+
+                self.stack.push(Concrete::Vessel(vessel));
+            },
+            Operatable::Discard => {
+                // Throws away the item on top of the stack, along with anything in it.
+                if let Concrete::System(System(vessel, _)) = self.pop(&op)? {
+                    self.vessels.empty(index, &vessel)?;
+                }
+            },
+            Operatable::Meld(_) => {
+                // Pours the system below into the system on top of the stack, leaving the original vessel empty.
+                let System(receiver, received) = self.pop(&op).and_then(|c| op.expect_system(c))?;
+                let System(source, poured) = self.pop(&op).and_then(|c| op.expect_system(c))?;
+
+                let mixture = Mixture::Compound(Box::new(received), Box::new(poured));
+                self.vessels.empty(index, &source)?;
+                self.vessels.refill(index, &receiver, &mixture);
+
+                self.stack.push(Concrete::Vessel(source));
+                self.stack.push(Concrete::System(System(receiver, mixture)));
+            },
+        }
+
+        self.trace.push(TraceStep { operatable, stack: self.stack.clone() });
+
+        Ok(())
+    }
+
+    /// Finishes evaluation, which must leave exactly one item on the stack, and no tagged items unused.
+    pub fn finish(mut self) -> Result<Evaluation, Error> {
+        if let Some((tag, &(index, _))) = self.tags.iter().min_by_key(|&(_, &(index, _))| index) {
+            Err(TagError::Unused{index, tag: tag.clone()})?;
+        }
+
+        match self.stack.len() {
+            0 => Err(EvaluationError::Empty)?,
+            1 if Kind::of(&self.stack[0]).is_dish() => Ok(Evaluation { result: self.stack.remove(0), trace: self.trace, vessels: self.vessels }),
+            1 => Err(EvaluationError::NotADish{found: Box::new(self.stack.remove(0))})?,
+            _ => Err(EvaluationError::Leftover{leftover: self.stack})?,
+        }
+    }
+
+    // Describes why the stack does not fit the signature of an operation, in terms of the concrete items on it.
+    fn signature_error(&self, op: &Op, signature: Signature, error: SignatureError) -> Error {
+        match error {
+            SignatureError::Underflow{needed, found} => op.underflow(needed, found).into(),
+            SignatureError::Mismatch{depth, expected, ..} => {
+                let found = self.stack[self.stack.len() - 1 - depth].clone();
+
+                match (signature, expected) {
+                    (Signature::Configure, _) => op.mismatch("a vessel", found),
+                    (_, Kind::System) if Op::is_vessel(&found) => op.expect_system(found).unwrap_err(),
+                    _ => op.mismatch(expected.description(), found),
+                }
+            },
+        }
+    }
+
+    fn pop(&mut self, op: &Op) -> Result<Concrete, Error> {
+        let item = self.stack.pop().ok_or_else(|| op.underflow(1, 0))?;
+
+        Ok(item)
+    }
+}
+
+// The operation currently being performed, used to describe errors.
+struct Op<'a> {
+    index: usize,
+    operatable: &'a Operatable,
+}
+
+impl<'a> Op<'a> {
+    fn underflow(&self, needed: usize, found: usize) -> EvaluationError {
+        EvaluationError::Underflow{index: self.index, operatable: self.operatable.clone(), needed, found}
+    }
+
+    fn mismatch(&self, expected: &'static str, found: Concrete) -> Error {
+        EvaluationError::TypeMismatch{index: self.index, operatable: self.operatable.clone(), expected, found: Box::new(found)}.into()
+    }
+
+    fn is_vessel(concrete: &Concrete) -> bool {
+        matches!(*concrete, Concrete::Container(_) | Concrete::Appliance(_) | Concrete::Vessel(_))
+    }
+
+    fn expect_mixture(&self, concrete: Concrete) -> Result<Mixture, Error> {
+        match concrete {
+            Concrete::Ingredient(i) => Ok(Mixture::Ingredient(i)),
+            Concrete::Mixture(m) => Ok(m),
+            other => Err(self.mismatch("a mixture", other)),
+        }
+    }
+
+    fn expect_vessel(&self, concrete: Concrete) -> Result<Vessel, Error> {
+        match concrete {
+            Concrete::Container(c) => Ok(Vessel::Container(c)),
+            Concrete::Appliance(a) => Ok(Vessel::Appliance(a)),
+            Concrete::Vessel(v) => Ok(v),
+            other => Err(self.mismatch("an empty vessel", other)),
+        }
+    }
+
+    fn expect_system(&self, concrete: Concrete) -> Result<System, Error> {
+        match concrete {
+            Concrete::System(s) => Ok(s),
+            other if Op::is_vessel(&other) => {
+                let vessel = self.expect_vessel(other)?;
+                Err(VesselError::Empty{index: self.index, vessel: vessel.name().to_string()}.into())
+            },
+            other => Err(self.mismatch("a filled vessel", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, EvaluationError, TraceStep};
+    use super::super::*;
+    use super::super::tag::TagError;
+    use super::super::vessel::VesselError;
+
+    use num_rational::Ratio;
+
+    use types::unit::Unit;
+
+    fn ingredient(name: &str) -> Ingredient {
+        Ingredient(name.to_string(), vec![], vec![])
+    }
+
+    fn bowl() -> Container {
+        Container("bowl".to_string(), vec![], vec![])
+    }
+
+    fn verb(name: &str) -> Verb {
+        Verb(name.to_string(), vec![], vec![])
+    }
+
+    fn knife() -> Tool {
+        Tool("knife".to_string(), vec![], vec![])
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let apple = || Operatable::Ingredient(ingredient("apple"));
+        let pear = || Operatable::Ingredient(ingredient("pear"));
+        let m_apple = || Box::new(Mixture::Ingredient(ingredient("apple")));
+        let m_pear = || Box::new(Mixture::Ingredient(ingredient("pear")));
+        let combine = || Operatable::Combine(Combine("mix".to_string(), vec![], vec![]));
+        let transfer = || Operatable::Transfer(Transfer("pour".to_string(), vec![], vec![]));
+
+        let inputs_and_expected = vec![
+            (vec![apple()], Some(Concrete::Ingredient(ingredient("apple")))),
+            (vec![apple(), pear(), combine()], Some(Concrete::Mixture(Mixture::Compound(m_apple(), m_pear())))),
+            (vec![apple(), Operatable::Verb(verb("slice"))], Some(Concrete::Mixture(Mixture::Processed(m_apple(), verb("slice"), None)))),
+            (
+                vec![apple(), Operatable::Tool(knife()), Operatable::Verb(verb("slice"))],
+                Some(Concrete::Mixture(Mixture::Processed(m_apple(), verb("slice"), Some(knife())))),
+            ),
+            (
+                vec![apple(), Operatable::Measure(Quantity::whole(2, Unit::Each))],
+                Some(Concrete::Mixture(Mixture::Measured(m_apple(), Quantity::new(Ratio::from_integer(2), Unit::Each)))),
+            ),
+            (
+                vec![apple(), Operatable::Container(bowl()), Operatable::Place],
+                Some(Concrete::System(System(Vessel::Container(bowl()), *m_apple()))),
+            ),
+            (
+                vec![Operatable::Container(bowl()), apple(), Operatable::Place, Operatable::Verb(verb("toss"))],
+                Some(Concrete::System(System(Vessel::Container(bowl()), Mixture::Processed(m_apple(), verb("toss"), None)))),
+            ),
+            (
+                vec![apple(), Operatable::Container(bowl()), Operatable::Place, Operatable::Remove, Operatable::Verb(verb("slice"))],
+                None,
+            ),
+            (
+                vec![
+                    apple(), Operatable::Container(bowl()), Operatable::Place,
+                    Operatable::Appliance(Appliance("pan".to_string(), vec![], vec![])), transfer(),
+                    Operatable::Remove, pear(), combine(),
+                ],
+                None,
+            ),
+            (
+                vec![apple(), Operatable::TagSet("fruit".to_string()), pear(), Operatable::TagGet("fruit".to_string()), combine()],
+                Some(Concrete::Mixture(Mixture::Compound(m_pear(), m_apple()))),
+            ),
+            (
+                vec![apple(), Operatable::Take(Portion::Fraction(1, 3)), Operatable::TagSet("reserved".to_string()), Operatable::Verb(verb("slice")),
+                    Operatable::TagGet("reserved".to_string()), combine()],
+                Some(Concrete::Mixture(Mixture::Compound(
+                    Box::new(Mixture::Processed(Box::new(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(2, 3)))), verb("slice"), None)),
+                    Box::new(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(1, 3)))),
+                ))),
+            ),
+            (vec![], None),
+            (vec![combine()], None),
+            (vec![apple(), Operatable::Container(bowl()), combine()], None),
+            (vec![apple(), apple(), Operatable::Place], None),
+            (vec![apple(), Operatable::Discard], None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Evaluator::evaluate(input).ok().map(|e| e.result);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_take_and_leave() {
+        let apple = Operatable::Ingredient(ingredient("apple"));
+        let m_apple = || Box::new(Mixture::Ingredient(ingredient("apple")));
+        let handful = || Portion::Pseudo("a handful".to_string());
+
+        let inputs_and_expected = vec![
+            (
+                Operatable::Take(Portion::Fraction(1, 4)),
+                vec![
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(3, 4)))),
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(1, 4)))),
+                ],
+            ),
+            (
+                Operatable::Leave(Portion::Fraction(1, 4)),
+                vec![
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(1, 4)))),
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Portion(Portion::Fraction(3, 4)))),
+                ],
+            ),
+            (
+                Operatable::Take(handful()),
+                vec![
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Rest(handful()))),
+                    Concrete::Mixture(Mixture::Portioned(m_apple(), Part::Portion(handful()))),
+                ],
+            ),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut evaluator = Evaluator::new();
+            evaluator.step(0, apple.clone()).expect("Unable to step");
+            evaluator.step(1, input).expect("Unable to step");
+            assert_eq!(&expected[..], evaluator.stack());
+        }
+    }
+
+    #[test]
+    fn test_vessels() {
+        let apple = || Operatable::Ingredient(ingredient("apple"));
+        let m_apple = || Mixture::Ingredient(ingredient("apple"));
+        let oven = || Appliance("oven".to_string(), vec![], vec![]);
+
+        let evaluation = Evaluator::evaluate(vec![
+            apple(), Operatable::Container(bowl()), Operatable::Place,
+            Operatable::Appliance(oven()), Operatable::Configure("200 °C".to_string()),
+            Operatable::Transfer(Transfer("tip".to_string(), vec![], vec![])),
+            Operatable::TagSet("baking".to_string()), Operatable::Discard, Operatable::TagGet("baking".to_string()),
+        ]).expect("Unable to evaluate");
+
+        assert_eq!(Concrete::System(System(Vessel::Appliance(oven()), m_apple())), evaluation.result);
+
+        let bowl_state = evaluation.vessels.get("bowl").expect("Unknown vessel");
+        assert_eq!(&[(2, Some(m_apple())), (5, None)], bowl_state.history());
+
+        let oven_state = evaluation.vessels.get("oven").expect("Unknown vessel");
+        assert_eq!(Some(&m_apple()), oven_state.contents());
+        assert_eq!(&["200 °C".to_string()], oven_state.settings());
+
+        let inputs_and_expected = vec![
+            (
+                vec![apple(), Operatable::Container(bowl()), Operatable::Place, apple(), Operatable::Container(bowl()), Operatable::Place],
+                VesselError::Occupied{index: 5, vessel: "bowl".to_string()},
+            ),
+            (vec![Operatable::Container(bowl()), Operatable::Remove], VesselError::Empty{index: 1, vessel: "bowl".to_string()}),
+            (vec![Operatable::Container(bowl()), Operatable::Empty], VesselError::Empty{index: 1, vessel: "bowl".to_string()}),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Evaluator::evaluate(input)
+                .unwrap_err()
+                .downcast::<VesselError>()
+                .expect("Unexpected error type");
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_meld() {
+        let pan = || Appliance("pan".to_string(), vec![], vec![]);
+        let sauce = || Mixture::Ingredient(ingredient("sauce"));
+        let pasta = || Mixture::Ingredient(ingredient("pasta"));
+        let meld = || Operatable::Meld("toss".to_string());
+
+        let mut evaluator = Evaluator::new();
+        let ops = vec![
+            Operatable::Ingredient(ingredient("sauce")), Operatable::Container(bowl()), Operatable::Place,
+            Operatable::Ingredient(ingredient("pasta")), Operatable::Appliance(pan()), Operatable::Place,
+            meld(),
+        ];
+
+        for (index, op) in ops.into_iter().enumerate() {
+            evaluator.step(index, op).expect("Unable to step");
+        }
+
+        let expected = [
+            Concrete::Vessel(Vessel::Container(bowl())),
+            Concrete::System(System(Vessel::Appliance(pan()), Mixture::Compound(Box::new(pasta()), Box::new(sauce())))),
+        ];
+        assert_eq!(&expected[..], evaluator.stack());
+        assert!(!evaluator.vessels().is_occupied(&Vessel::Container(bowl())));
+        assert!(evaluator.vessels().is_occupied(&Vessel::Appliance(pan())));
+
+        let produced = Evaluator::evaluate(vec![Operatable::Ingredient(ingredient("sauce")), Operatable::Container(bowl()), Operatable::Place,
+            Operatable::Ingredient(ingredient("pasta")), meld()])
+            .unwrap_err()
+            .downcast::<EvaluationError>()
+            .expect("Unexpected error type");
+        let expected = EvaluationError::TypeMismatch{index: 4, operatable: meld(), expected: "a filled vessel", found: Box::new(Concrete::Ingredient(ingredient("pasta")))};
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_trace() {
+        let apple = Operatable::Ingredient(ingredient("apple"));
+        let slice = Operatable::Verb(verb("slice"));
+
+        let produced = Evaluator::evaluate(vec![apple.clone(), slice.clone()]).expect("Unable to evaluate").trace;
+        let expected = vec![
+            TraceStep { operatable: apple, stack: vec![Concrete::Ingredient(ingredient("apple"))] },
+            TraceStep {
+                operatable: slice,
+                stack: vec![Concrete::Mixture(Mixture::Processed(Box::new(Mixture::Ingredient(ingredient("apple"))), verb("slice"), None))],
+            },
+        ];
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_errors() {
+        let apple = || Operatable::Ingredient(ingredient("apple"));
+        let combine = Operatable::Combine(Combine("mix".to_string(), vec![], vec![]));
+
+        let inputs_and_expected = vec![
+            (vec![apple(), combine.clone()], EvaluationError::Underflow{index: 1, operatable: combine.clone(), needed: 2, found: 1}),
+            (vec![Operatable::Verb(verb("slice"))], EvaluationError::Underflow{index: 0, operatable: Operatable::Verb(verb("slice")), needed: 1, found: 0}),
+            (
+                vec![Operatable::Tool(knife()), Operatable::Verb(verb("slice"))],
+                EvaluationError::Underflow{index: 1, operatable: Operatable::Verb(verb("slice")), needed: 2, found: 1},
+            ),
+            (
+                vec![Operatable::Container(bowl()), apple(), combine.clone()],
+                EvaluationError::TypeMismatch{index: 2, operatable: combine.clone(), expected: "a mixture", found: Box::new(Concrete::Container(bowl()))},
+            ),
+            (
+                vec![apple(), Operatable::Remove],
+                EvaluationError::TypeMismatch{index: 1, operatable: Operatable::Remove, expected: "a filled vessel", found: Box::new(Concrete::Ingredient(ingredient("apple")))},
+            ),
+            (vec![Operatable::Meld("stir".to_string())], EvaluationError::Underflow{index: 0, operatable: Operatable::Meld("stir".to_string()), needed: 2, found: 0}),
+            (vec![Operatable::Discard], EvaluationError::Underflow{index: 0, operatable: Operatable::Discard, needed: 1, found: 0}),
+            (
+                vec![apple(), Operatable::Configure("high".to_string())],
+                EvaluationError::TypeMismatch{
+                    index: 1,
+                    operatable: Operatable::Configure("high".to_string()),
+                    expected: "a vessel",
+                    found: Box::new(Concrete::Ingredient(ingredient("apple"))),
+                },
+            ),
+            (vec![], EvaluationError::Empty),
+            (vec![apple(), apple()], EvaluationError::Leftover{leftover: vec![Concrete::Ingredient(ingredient("apple")); 2]}),
+            (vec![Operatable::Container(bowl())], EvaluationError::NotADish{found: Box::new(Concrete::Container(bowl()))}),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Evaluator::evaluate(input)
+                .unwrap_err()
+                .downcast::<EvaluationError>()
+                .expect("Unexpected error type");
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_tag_errors() {
+        let apple = || Operatable::Ingredient(ingredient("apple"));
+        let set = |t: &str| Operatable::TagSet(t.to_string());
+        let get = |t: &str| Operatable::TagGet(t.to_string());
+        let s = |s: &str| s.to_string();
+
+        let inputs_and_expected = vec![
+            (vec![apple(), get("sauce")], TagError::Unknown{index: 1, tag: s("sauce")}),
+            (vec![get("sauce"), apple(), set("sauce")], TagError::UsedBeforeSet{index: 0, tag: s("sauce")}),
+            (vec![apple(), set("sauce"), get("sauce"), get("sauce")], TagError::Reused{index: 3, tag: s("sauce")}),
+            (vec![apple(), set("sauce"), apple()], TagError::Unused{index: 1, tag: s("sauce")}),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Evaluator::evaluate(input)
+                .unwrap_err()
+                .downcast::<TagError>()
+                .expect("Unexpected error type");
+            assert_eq!(expected, produced);
+        }
+
+        // Stepping by hand skips the up-front check, so misuse is caught as it happens.
+        let mut evaluator = Evaluator::new();
+        evaluator.step(0, apple()).expect("Unable to step");
+        evaluator.step(1, set("sauce")).expect("Unable to step");
+        assert_eq!(Some(&Concrete::Ingredient(ingredient("apple"))), evaluator.tagged("sauce"));
+
+        let produced = evaluator.finish().unwrap_err().downcast::<TagError>().expect("Unexpected error type");
+        assert_eq!(TagError::Unused{index: 1, tag: s("sauce")}, produced);
+    }
+}
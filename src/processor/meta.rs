@@ -0,0 +1,135 @@
+use token::Token;
+
+#[derive(Debug, Fail, PartialEq, Eq, Clone)]
+pub enum MetaError {
+    #[fail(display = "modifier at position {} has no preceding element to attach to: {}", index, modifier)]
+    OrphanModifier {
+        index: usize,
+        modifier: String,
+    },
+
+    #[fail(display = "annotation at position {} has no preceding element to attach to: {}", index, annotation)]
+    OrphanAnnotation {
+        index: usize,
+        annotation: String,
+    },
+}
+
+/// A token, along with any modifiers and annotations that directly followed it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MetaToken {
+    token: Token,
+    modifiers: Vec<String>,
+    annotations: Vec<String>,
+}
+
+impl MetaToken {
+    pub fn new(token: Token) -> Self {
+        MetaToken { token, modifiers: vec![], annotations: vec![] }
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn modifiers(&self) -> &[String] {
+        &self.modifiers
+    }
+
+    pub fn annotations(&self) -> &[String] {
+        &self.annotations
+    }
+
+    pub fn into_parts(self) -> (Token, Vec<String>, Vec<String>) {
+        (self.token, self.modifiers, self.annotations)
+    }
+
+    /// Whether modifiers and annotations may be attached to this token.
+    /// Only named elements can be described in this way; operators such as `Place` cannot.
+    pub fn is_attachable(&self) -> bool {
+        matches!(self.token,
+            Token::Ingredient(_)
+            | Token::Tool(_)
+            | Token::Container(_)
+            | Token::Appliance(_)
+            | Token::Verb(_)
+            | Token::Combine(_)
+            | Token::Transfer(_)
+        )
+    }
+}
+
+/// Folds each modifier and annotation into the element preceding it.
+/// Modifiers and annotations that have no element to attach to are dropped, and reported as errors.
+/// Positions in errors are indices into the original token sequence.
+pub fn process_meta<II>(tokens: II) -> (Vec<MetaToken>, Vec<MetaError>)
+where II: IntoIterator<Item = Token>
+{
+    let mut meta_tokens: Vec<MetaToken> = vec![];
+    let mut errors = vec![];
+
+    for (index, token) in tokens.into_iter().enumerate() {
+        let target = meta_tokens.last_mut().filter(|mt| mt.is_attachable());
+
+        match (token, target) {
+            (Token::Modifier(modifier), Some(target)) => target.modifiers.push(modifier),
+            (Token::Annotation(annotation), Some(target)) => target.annotations.push(annotation),
+            (Token::Modifier(modifier), None) => errors.push(MetaError::OrphanModifier{index, modifier}),
+            (Token::Annotation(annotation), None) => errors.push(MetaError::OrphanAnnotation{index, annotation}),
+            (token, _) => meta_tokens.push(MetaToken::new(token)),
+        }
+    }
+
+    (meta_tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetaToken, MetaError, process_meta};
+
+    use token::Token;
+
+    fn meta(token: Token, modifiers: &[&str], annotations: &[&str]) -> MetaToken {
+        MetaToken {
+            token,
+            modifiers: modifiers.iter().map(|s| s.to_string()).collect(),
+            annotations: annotations.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_process_meta() {
+        let apple = || Token::Ingredient("apple".to_string());
+        let slice = || Token::Verb("slice".to_string());
+        let modifier = |s: &str| Token::Modifier(s.to_string());
+        let annotation = |s: &str| Token::Annotation(s.to_string());
+
+        let inputs_and_expected = vec![
+            (vec![], (vec![], vec![])),
+            (vec![apple(), slice()], (vec![meta(apple(), &[], &[]), meta(slice(), &[], &[])], vec![])),
+            (
+                vec![apple(), modifier("red"), annotation("ripe"), modifier("large"), slice(), annotation("thinly")],
+                (vec![meta(apple(), &["red", "large"], &["ripe"]), meta(slice(), &[], &["thinly"])], vec![]),
+            ),
+            (
+                vec![modifier("red"), apple()],
+                (vec![meta(apple(), &[], &[])], vec![MetaError::OrphanModifier{index: 0, modifier: "red".to_string()}]),
+            ),
+            (
+                vec![apple(), Token::Place, annotation("gently"), modifier("red")],
+                (
+                    vec![meta(apple(), &[], &[]), meta(Token::Place, &[], &[])],
+                    vec![
+                        MetaError::OrphanAnnotation{index: 2, annotation: "gently".to_string()},
+                        MetaError::OrphanModifier{index: 3, modifier: "red".to_string()},
+                    ],
+                ),
+            ),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = process_meta(input);
+            assert_eq!(expected, produced);
+        }
+    }
+}
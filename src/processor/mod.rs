@@ -1,7 +1,18 @@
+pub mod meta;
+pub mod eval;
+pub mod check;
+pub mod signature;
+pub mod tag;
+pub mod vessel;
+
 use failure::Error;
 
+use num_rational::Ratio;
+
 use token::Token;
 use types::{Portion, Quantity};
+use types::unit::Amount;
+use self::meta::{MetaError, process_meta};
 
 /*
 Ingredient
@@ -31,6 +42,24 @@ Time
 Until
 */
 
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum OperatableError {
+    #[fail(display = "token at position {} cannot stand alone: {:?}", index, token)]
+    Standalone {
+        index: usize,
+        token: Token,
+    },
+}
+
+impl From<MetaError> for OperatableError {
+    fn from(error: MetaError) -> Self {
+        match error {
+            MetaError::OrphanModifier{index, modifier} => OperatableError::Standalone{index, token: Token::Modifier(modifier)},
+            MetaError::OrphanAnnotation{index, annotation} => OperatableError::Standalone{index, token: Token::Annotation(annotation)},
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Element types
 
@@ -58,10 +87,61 @@ pub struct Transfer(String, Vec<String>, Vec<String>);
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Derived types
 
+/// One of the two parts of a mixture that has been divided by taking or leaving a portion of it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Part {
+    /// The given portion of the mixture.
+    Portion(Portion),
+    /// Whatever remained after the given portion was divided off.
+    Rest(Portion),
+}
+
+impl Part {
+    /// Divides a mixture into the given portion and the rest.
+    /// The rest of a fractional portion is itself a fraction, e.g. the rest of 1/3 is 2/3.
+    pub fn split(portion: Portion) -> (Part, Part) {
+        match portion {
+            Portion::Fraction(n, d) => (Part::Portion(Portion::Fraction(n, d)), Part::Portion(Portion::Fraction(d - n, d))),
+            portion => (Part::Portion(portion.clone()), Part::Rest(portion)),
+        }
+    }
+
+    /// The fraction of the divided mixture that is in this part, if known.
+    pub fn fraction(&self) -> Option<Amount> {
+        match *self {
+            Part::Portion(Portion::Fraction(n, d)) => Some(Ratio::new(n.into(), d.into())),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Mixture {
     Ingredient(Ingredient),
     Compound(Box<Mixture>, Box<Mixture>),
+    Processed(Box<Mixture>, Verb, Option<Tool>),
+    Measured(Box<Mixture>, Quantity),
+    Portioned(Box<Mixture>, Part),
+}
+
+impl Mixture {
+    /// Lists the ingredients in this mixture, along with the fraction of each that ended up in it.
+    /// The fraction is unknown if the mixture was divided by a quantity or by an unquantified portion.
+    pub fn ingredients(&self) -> Vec<(&Ingredient, Option<Amount>)> {
+        match *self {
+            Mixture::Ingredient(ref i) => vec![(i, Some(Ratio::from_integer(1)))],
+            Mixture::Compound(ref a, ref b) => a.ingredients().into_iter().chain(b.ingredients()).collect(),
+            Mixture::Processed(ref m, ..) | Mixture::Measured(ref m, _) => m.ingredients(),
+            Mixture::Portioned(ref m, ref part) => {
+                let fraction = part.fraction();
+
+                m.ingredients()
+                    .into_iter()
+                    .map(|(i, f)| (i, f.and_then(|f| fraction.map(|p| f * p))))
+                    .collect()
+            },
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -70,6 +150,15 @@ pub enum Vessel {
     Appliance(Appliance),
 }
 
+impl Vessel {
+    pub fn name(&self) -> &str {
+        match *self {
+            Vessel::Container(Container(ref name, ..)) => name,
+            Vessel::Appliance(Appliance(ref name, ..)) => name,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct System(Vessel, Mixture);
 
@@ -77,6 +166,7 @@ pub struct System(Vessel, Mixture);
 // Stack items
 
 /// The possible elements remaining after meta processing.
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Operatable {
     Ingredient(Ingredient),
     Tool(Tool),
@@ -99,15 +189,50 @@ pub enum Operatable {
 }
 
 impl Operatable {
-    pub fn create_operatable_stack<II: IntoIterator<Item = Token>>(tokens: II) -> Vec<Operatable> {
-        tokens
+    /// Converts a sequence of tokens, such as a single walk through a flow, into operatables.
+    /// Modifiers and annotations are folded into the element they follow; any that do not follow an element are an error.
+    pub fn create_operatable_stack<II: IntoIterator<Item = Token>>(tokens: II) -> Result<Vec<Operatable>, Error> {
+        let (meta_tokens, errors) = process_meta(tokens);
+
+        if let Some(error) = errors.into_iter().next() {
+            Err(OperatableError::from(error))?;
+        }
+
+        let operatables = meta_tokens
             .into_iter()
-            .map(|_| Operatable::Place)
-            .collect::<Vec<_>>()
+            .map(|meta_token| {
+                let (token, mods, anns) = meta_token.into_parts();
+
+                match token {
+                    Token::Ingredient(s) => Operatable::Ingredient(Ingredient(s, mods, anns)),
+                    Token::Tool(s) => Operatable::Tool(Tool(s, mods, anns)),
+                    Token::Container(s) => Operatable::Container(Container(s, mods, anns)),
+                    Token::Appliance(s) => Operatable::Appliance(Appliance(s, mods, anns)),
+                    Token::Verb(s) => Operatable::Verb(Verb(s, mods, anns)),
+                    Token::Combine(s) => Operatable::Combine(Combine(s, mods, anns)),
+                    Token::Transfer(s) => Operatable::Transfer(Transfer(s, mods, anns)),
+                    Token::Measure(q) => Operatable::Measure(q),
+                    Token::Take(p) => Operatable::Take(p),
+                    Token::Leave(p) => Operatable::Leave(p),
+                    Token::Place => Operatable::Place,
+                    Token::Remove => Operatable::Remove,
+                    Token::Configure(s) => Operatable::Configure(s),
+                    Token::Meld(s) => Operatable::Meld(s),
+                    Token::Discard => Operatable::Discard,
+                    Token::Empty => Operatable::Empty,
+                    Token::TagSet(s) => Operatable::TagSet(s),
+                    Token::TagGet(s) => Operatable::TagGet(s),
+                    Token::Modifier(_) | Token::Annotation(_) => unreachable!("modifiers and annotations are folded during meta processing"),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(operatables)
     }
 }
 
 /// The possible elements remaining after operator processing.
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Concrete {
     Ingredient(Ingredient),
     Tool(Tool),
@@ -117,3 +242,100 @@ pub enum Concrete {
     Vessel(Vessel),
     System(System),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use types::unit::Unit;
+
+    #[test]
+    fn test_create_operatable_stack() {
+        let s = |s: &str| s.to_string();
+
+        let inputs_and_expected = vec![
+            (vec![], Some(vec![])),
+            (
+                vec![
+                    Token::Ingredient(s("apple")), Token::Modifier(s("red")), Token::Annotation(s("ripe")),
+                    Token::Container(s("bowl")),
+                    Token::Place,
+                    Token::Verb(s("toss")), Token::Annotation(s("gently")),
+                    Token::Measure(Quantity::whole(2, Unit::Cup)),
+                    Token::Take(Portion::Fraction(1, 3)),
+                    Token::TagSet(s("salad")),
+                ],
+                Some(vec![
+                    Operatable::Ingredient(Ingredient(s("apple"), vec![s("red")], vec![s("ripe")])),
+                    Operatable::Container(Container(s("bowl"), vec![], vec![])),
+                    Operatable::Place,
+                    Operatable::Verb(Verb(s("toss"), vec![], vec![s("gently")])),
+                    Operatable::Measure(Quantity::new(Ratio::from_integer(2), Unit::Cup)),
+                    Operatable::Take(Portion::Fraction(1, 3)),
+                    Operatable::TagSet(s("salad")),
+                ]),
+            ),
+            (vec![Token::Modifier(s("red"))], None),
+            (vec![Token::Ingredient(s("apple")), Token::Discard, Token::Annotation(s("quickly"))], None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Operatable::create_operatable_stack(input).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_ingredients() {
+        let apple = || Mixture::Ingredient(Ingredient("apple".to_string(), vec![], vec![]));
+        let pear = || Mixture::Ingredient(Ingredient("pear".to_string(), vec![], vec![]));
+        let portioned = |m: Mixture, p: Part| Mixture::Portioned(Box::new(m), p);
+        let compound = |a: Mixture, b: Mixture| Mixture::Compound(Box::new(a), Box::new(b));
+
+        let inputs_and_expected = vec![
+            (apple(), vec![("apple", Some(Ratio::from_integer(1)))]),
+            (portioned(apple(), Part::Portion(Portion::Fraction(1, 3))), vec![("apple", Some(Ratio::new(1, 3)))]),
+            (
+                portioned(compound(portioned(apple(), Part::Portion(Portion::Fraction(1, 2))), pear()), Part::Portion(Portion::Fraction(2, 3))),
+                vec![("apple", Some(Ratio::new(1, 3))), ("pear", Some(Ratio::new(2, 3)))],
+            ),
+            (
+                compound(portioned(apple(), Part::Rest(Portion::Quantity(Quantity::whole(200, Unit::Gram)))), pear()),
+                vec![("apple", None), ("pear", Some(Ratio::from_integer(1)))],
+            ),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced: Vec<_> = input.ingredients().into_iter().map(|(i, f)| (i.0.as_str(), f)).collect();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let inputs_and_expected = vec![
+            (Portion::Fraction(1, 3), (Part::Portion(Portion::Fraction(1, 3)), Part::Portion(Portion::Fraction(2, 3)))),
+            (Portion::Pseudo("a handful".to_string()), (
+                Part::Portion(Portion::Pseudo("a handful".to_string())),
+                Part::Rest(Portion::Pseudo("a handful".to_string())),
+            )),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Part::split(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_standalone_error() {
+        let tokens = vec![Token::Place, Token::Modifier("red".to_string())];
+        let produced = Operatable::create_operatable_stack(tokens)
+            .unwrap_err()
+            .downcast::<OperatableError>()
+            .expect("Unexpected error type");
+        let expected = OperatableError::Standalone{index: 1, token: Token::Modifier("red".to_string())};
+
+        assert_eq!(expected, produced);
+    }
+}
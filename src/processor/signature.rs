@@ -0,0 +1,240 @@
+use std::fmt;
+
+use token::Token;
+use super::{Operatable, Concrete};
+
+/// The kind of a stack item, as far as can be known without evaluating anything.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Kind {
+    Mixture,
+    Tool,
+    Vessel,
+    System,
+}
+
+impl Kind {
+    pub fn of(concrete: &Concrete) -> Self {
+        match *concrete {
+            Concrete::Ingredient(_) | Concrete::Mixture(_) => Kind::Mixture,
+            Concrete::Tool(_) => Kind::Tool,
+            Concrete::Container(_) | Concrete::Appliance(_) | Concrete::Vessel(_) => Kind::Vessel,
+            Concrete::System(_) => Kind::System,
+        }
+    }
+
+    /// Whether an item of this kind may be served as a finished dish.
+    pub fn is_dish(self) -> bool {
+        matches!(self, Kind::Mixture | Kind::System)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Kind::Mixture => "a mixture",
+            Kind::Tool => "a tool",
+            Kind::Vessel => "an empty vessel",
+            Kind::System => "a filled vessel",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+/// Why a stack does not fit a signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SignatureError {
+    Underflow {
+        needed: usize,
+        found: usize,
+    },
+
+    /// The item `depth` places below the top of the stack, as it was before the operation, is of the wrong kind.
+    Mismatch {
+        depth: usize,
+        expected: Kind,
+        found: Kind,
+    },
+}
+
+/// The stack effect of an operation, shared by the checker and the evaluator.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Signature {
+    /// Pops items of the given kinds and pushes items of the given kinds, both listed from the bottom of the stack up.
+    Fixed(&'static [Kind], &'static [Kind]),
+
+    /// Processes the mixture or filled vessel on top of the stack, keeping its kind.
+    /// A tool on top of the stack is used and put away.
+    Process,
+
+    /// Pops a vessel and a mixture in either order, pushing a filled vessel.
+    Place,
+
+    /// Requires a vessel on top of the stack, whether or not it is holding anything, and leaves it there.
+    Configure,
+
+    /// Pops an item of any kind.
+    Drop,
+
+    /// Pushes an item that was set aside earlier, whose kind is only known from its tag.
+    Restore,
+}
+
+// Elements push themselves.
+const MIXTURE: Signature = Signature::Fixed(&[], &[Kind::Mixture]);
+const TOOL: Signature = Signature::Fixed(&[], &[Kind::Tool]);
+const VESSEL: Signature = Signature::Fixed(&[], &[Kind::Vessel]);
+
+const COMBINE: Signature = Signature::Fixed(&[Kind::Mixture, Kind::Mixture], &[Kind::Mixture]);
+const MEASURE: Signature = Signature::Fixed(&[Kind::Mixture], &[Kind::Mixture]);
+// The part carried on with ends up on top.
+const PORTION: Signature = Signature::Fixed(&[Kind::Mixture], &[Kind::Mixture, Kind::Mixture]);
+const REMOVE: Signature = Signature::Fixed(&[Kind::System], &[Kind::Vessel, Kind::Mixture]);
+const EMPTY: Signature = Signature::Fixed(&[Kind::System], &[Kind::Vessel]);
+// Both leave the original vessel empty below the one now holding its contents.
+const TRANSFER: Signature = Signature::Fixed(&[Kind::System, Kind::Vessel], &[Kind::Vessel, Kind::System]);
+const MELD: Signature = Signature::Fixed(&[Kind::System, Kind::System], &[Kind::Vessel, Kind::System]);
+
+impl Signature {
+    /// The stack effect of a token, if it has one. Modifiers and annotations do not touch the stack.
+    pub fn of_token(token: &Token) -> Option<Self> {
+        let signature = match *token {
+            Token::Ingredient(_) => MIXTURE,
+            Token::Tool(_) => TOOL,
+            Token::Container(_) | Token::Appliance(_) => VESSEL,
+            Token::Verb(_) => Signature::Process,
+            Token::Combine(_) => COMBINE,
+            Token::Transfer(_) => TRANSFER,
+            Token::Measure(_) => MEASURE,
+            Token::Take(_) | Token::Leave(_) => PORTION,
+            Token::Place => Signature::Place,
+            Token::Remove => REMOVE,
+            Token::Configure(_) => Signature::Configure,
+            Token::Meld(_) => MELD,
+            Token::Discard | Token::TagSet(_) => Signature::Drop,
+            Token::Empty => EMPTY,
+            Token::TagGet(_) => Signature::Restore,
+            Token::Modifier(_) | Token::Annotation(_) => return None,
+        };
+
+        Some(signature)
+    }
+
+    pub fn of_operatable(operatable: &Operatable) -> Self {
+        match *operatable {
+            Operatable::Ingredient(_) => MIXTURE,
+            Operatable::Tool(_) => TOOL,
+            Operatable::Container(_) | Operatable::Appliance(_) => VESSEL,
+            Operatable::Verb(_) => Signature::Process,
+            Operatable::Combine(_) => COMBINE,
+            Operatable::Transfer(_) => TRANSFER,
+            Operatable::Measure(_) => MEASURE,
+            Operatable::Take(_) | Operatable::Leave(_) => PORTION,
+            Operatable::Place => Signature::Place,
+            Operatable::Remove => REMOVE,
+            Operatable::Configure(_) => Signature::Configure,
+            Operatable::Meld(_) => MELD,
+            Operatable::Discard | Operatable::TagSet(_) => Signature::Drop,
+            Operatable::Empty => EMPTY,
+            Operatable::TagGet(_) => Signature::Restore,
+        }
+    }
+
+    /// Applies this stack effect to a stack of kinds. Items are checked from the top of the stack down.
+    /// Restoring an item is left to the caller, which knows what was set aside.
+    pub fn apply(self, stack: &mut Vec<Kind>) -> Result<(), SignatureError> {
+        let mut popper = Popper { stack, depth: 0 };
+
+        match self {
+            Signature::Fixed(pops, pushes) => {
+                popper.require(pops.len())?;
+
+                for &expected in pops.iter().rev() {
+                    popper.pop_expecting(expected)?;
+                }
+
+                popper.stack.extend_from_slice(pushes);
+            },
+            Signature::Process => {
+                if popper.stack.last() == Some(&Kind::Tool) {
+                    popper.require(2)?;
+                    popper.pop()?;
+                }
+
+                let kind = match popper.pop()? {
+                    Kind::System => Kind::System,
+                    found => popper.expect(Kind::Mixture, found)?,
+                };
+
+                popper.stack.push(kind);
+            },
+            Signature::Place => {
+                // The vessel and the mixture may be given in either order.
+                popper.require(2)?;
+
+                match popper.stack[popper.stack.len() - 2] {
+                    Kind::Vessel => {
+                        popper.pop_expecting(Kind::Mixture)?;
+                        popper.pop()?;
+                    },
+                    _ => {
+                        popper.pop_expecting(Kind::Vessel)?;
+                        popper.pop_expecting(Kind::Mixture)?;
+                    },
+                }
+
+                popper.stack.push(Kind::System);
+            },
+            Signature::Configure => {
+                match popper.stack.last() {
+                    Some(&Kind::System) => {},
+                    Some(&top) => { popper.expect(Kind::Vessel, top)?; },
+                    None => return Err(SignatureError::Underflow{needed: 1, found: 0}),
+                }
+            },
+            Signature::Drop => {
+                popper.pop()?;
+            },
+            Signature::Restore => {},
+        }
+
+        Ok(())
+    }
+}
+
+// Pops items off a stack of kinds, keeping track of how far down the original stack it has gotten.
+struct Popper<'a> {
+    stack: &'a mut Vec<Kind>,
+    depth: usize,
+}
+
+impl<'a> Popper<'a> {
+    fn require(&self, needed: usize) -> Result<(), SignatureError> {
+        match self.stack.len() < needed {
+            true => Err(SignatureError::Underflow{needed, found: self.stack.len()}),
+            false => Ok(()),
+        }
+    }
+
+    fn pop(&mut self) -> Result<Kind, SignatureError> {
+        let kind = self.stack.pop().ok_or(SignatureError::Underflow{needed: 1, found: 0})?;
+        self.depth += 1;
+
+        Ok(kind)
+    }
+
+    fn pop_expecting(&mut self, expected: Kind) -> Result<Kind, SignatureError> {
+        let found = self.pop()?;
+        self.expect(expected, found)
+    }
+
+    // Checks the item most recently popped, or the item on top of the stack if nothing has been popped yet.
+    fn expect(&self, expected: Kind, found: Kind) -> Result<Kind, SignatureError> {
+        match expected == found {
+            true => Ok(found),
+            false => Err(SignatureError::Mismatch{depth: self.depth.saturating_sub(1), expected, found}),
+        }
+    }
+}
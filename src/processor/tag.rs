@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use token::Token;
+use super::Operatable;
+
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum TagError {
+    #[fail(display = "tag at position {} is never set: {}", index, tag)]
+    Unknown {
+        index: usize,
+        tag: String,
+    },
+
+    #[fail(display = "tag at position {} is used before it is set: {}", index, tag)]
+    UsedBeforeSet {
+        index: usize,
+        tag: String,
+    },
+
+    #[fail(display = "tag at position {} was already used, and has not been set again: {}", index, tag)]
+    Reused {
+        index: usize,
+        tag: String,
+    },
+
+    #[fail(display = "tag set at position {} is never used: {}", index, tag)]
+    Unused {
+        index: usize,
+        tag: String,
+    },
+}
+
+/// A reference to a named intermediate product.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TagUse<'a> {
+    Set(&'a str),
+    Get(&'a str),
+}
+
+impl<'a> TagUse<'a> {
+    pub fn of_token(token: &'a Token) -> Option<Self> {
+        match *token {
+            Token::TagSet(ref tag) => Some(TagUse::Set(tag)),
+            Token::TagGet(ref tag) => Some(TagUse::Get(tag)),
+            _ => None,
+        }
+    }
+
+    pub fn of_operatable(operatable: &'a Operatable) -> Option<Self> {
+        match *operatable {
+            Operatable::TagSet(ref tag) => Some(TagUse::Set(tag)),
+            Operatable::TagGet(ref tag) => Some(TagUse::Get(tag)),
+            _ => None,
+        }
+    }
+}
+
+/// Checks that every tag is set before it is used, and used exactly once per time it is set.
+/// Each item is the tag use at that position, if any; errors are given in the order they are found.
+pub fn check_tags<'a, II>(uses: II) -> Vec<TagError>
+where II: IntoIterator<Item = Option<TagUse<'a>>>
+{
+    let uses: Vec<_> = uses.into_iter().collect();
+
+    let ever_set: BTreeSet<&str> = uses.iter()
+        .filter_map(|u| match *u {
+            Some(TagUse::Set(tag)) => Some(tag),
+            _ => None,
+        })
+        .collect();
+
+    // Tags currently holding a value, along with where they were set.
+    let mut live: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut used: BTreeSet<&str> = BTreeSet::new();
+    let mut errors = vec![];
+
+    for (index, tag_use) in uses.into_iter().enumerate() {
+        match tag_use {
+            Some(TagUse::Set(tag)) => {
+                // Setting a tag that still holds a value loses the earlier value.
+                if let Some(set_index) = live.insert(tag, index) {
+                    errors.push(TagError::Unused{index: set_index, tag: tag.to_string()});
+                }
+            },
+            Some(TagUse::Get(tag)) => {
+                if live.remove(tag).is_some() {
+                    used.insert(tag);
+                }
+                else if used.contains(tag) {
+                    errors.push(TagError::Reused{index, tag: tag.to_string()});
+                }
+                else if ever_set.contains(tag) {
+                    errors.push(TagError::UsedBeforeSet{index, tag: tag.to_string()});
+                }
+                else {
+                    errors.push(TagError::Unknown{index, tag: tag.to_string()});
+                }
+            },
+            None => {},
+        }
+    }
+
+    let mut unused: Vec<_> = live.into_iter().map(|(tag, index)| (index, tag)).collect();
+    unused.sort();
+    errors.extend(unused.into_iter().map(|(index, tag)| TagError::Unused{index, tag: tag.to_string()}));
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TagError, TagUse, check_tags};
+
+    #[test]
+    fn test_check_tags() {
+        let set = |t| Some(TagUse::Set(t));
+        let get = |t| Some(TagUse::Get(t));
+        let s = |s: &str| s.to_string();
+
+        let inputs_and_expected = vec![
+            (vec![], vec![]),
+            (vec![None, set("marinade"), None, get("marinade")], vec![]),
+            (vec![set("a"), get("a"), set("a"), get("a")], vec![]),
+            (vec![get("sauce")], vec![TagError::Unknown{index: 0, tag: s("sauce")}]),
+            (vec![get("sauce"), set("sauce")], vec![
+                TagError::UsedBeforeSet{index: 0, tag: s("sauce")},
+                TagError::Unused{index: 1, tag: s("sauce")},
+            ]),
+            (vec![set("sauce"), get("sauce"), get("sauce")], vec![TagError::Reused{index: 2, tag: s("sauce")}]),
+            (vec![set("b"), set("a"), None], vec![
+                TagError::Unused{index: 0, tag: s("b")},
+                TagError::Unused{index: 1, tag: s("a")},
+            ]),
+            (vec![set("a"), set("a"), get("a")], vec![TagError::Unused{index: 0, tag: s("a")}]),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = check_tags(input);
+            assert_eq!(expected, produced);
+        }
+    }
+}
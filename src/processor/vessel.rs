@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use super::{Mixture, Vessel};
+
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum VesselError {
+    #[fail(display = "vessel at position {} is already holding something: {}", index, vessel)]
+    Occupied {
+        index: usize,
+        vessel: String,
+    },
+
+    #[fail(display = "vessel at position {} has nothing in it: {}", index, vessel)]
+    Empty {
+        index: usize,
+        vessel: String,
+    },
+}
+
+/// What a single vessel holds and how it is set up, along with how its contents changed over time.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct VesselState {
+    contents: Option<Mixture>,
+    settings: Vec<String>,
+    history: Vec<(usize, Option<Mixture>)>,
+}
+
+impl VesselState {
+    pub fn contents(&self) -> Option<&Mixture> {
+        self.contents.as_ref()
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.contents.is_some()
+    }
+
+    /// The settings of this vessel, such as oven temperatures, in the order they were made.
+    pub fn settings(&self) -> &[String] {
+        &self.settings
+    }
+
+    /// Each change to the contents of this vessel, along with the position of the operation that caused it.
+    pub fn history(&self) -> &[(usize, Option<Mixture>)] {
+        &self.history
+    }
+
+    fn set_contents(&mut self, index: usize, contents: Option<Mixture>) {
+        self.history.push((index, contents.clone()));
+        self.contents = contents;
+    }
+}
+
+/// Tracks every vessel used during evaluation, by name.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Registry {
+    vessels: BTreeMap<String, VesselState>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VesselState> {
+        self.vessels.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &VesselState)> {
+        self.vessels.iter().map(|(name, state)| (name.as_str(), state))
+    }
+
+    /// Whether a vessel is currently holding something.
+    pub fn is_occupied(&self, vessel: &Vessel) -> bool {
+        self.get(vessel.name()).is_some_and(VesselState::is_occupied)
+    }
+
+    /// Puts a mixture into an empty vessel.
+    pub fn fill(&mut self, index: usize, vessel: &Vessel, mixture: &Mixture) -> Result<(), VesselError> {
+        let state = self.state(vessel);
+
+        if state.is_occupied() {
+            return Err(VesselError::Occupied{index, vessel: vessel.name().to_string()});
+        }
+
+        state.set_contents(index, Some(mixture.clone()));
+        Ok(())
+    }
+
+    /// Replaces the contents of a vessel in place, such as after stirring it.
+    pub fn refill(&mut self, index: usize, vessel: &Vessel, mixture: &Mixture) {
+        self.state(vessel).set_contents(index, Some(mixture.clone()));
+    }
+
+    /// Takes the contents out of a vessel, leaving it empty.
+    pub fn empty(&mut self, index: usize, vessel: &Vessel) -> Result<Mixture, VesselError> {
+        let state = self.state(vessel);
+
+        match state.contents.take() {
+            Some(mixture) => {
+                state.history.push((index, None));
+                Ok(mixture)
+            },
+            None => Err(VesselError::Empty{index, vessel: vessel.name().to_string()}),
+        }
+    }
+
+    pub fn configure(&mut self, vessel: &Vessel, setting: &str) {
+        self.state(vessel).settings.push(setting.to_string());
+    }
+
+    fn state(&mut self, vessel: &Vessel) -> &mut VesselState {
+        self.vessels.entry(vessel.name().to_string()).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, VesselError};
+    use super::super::*;
+
+    #[test]
+    fn test_registry() {
+        let bowl = Vessel::Container(Container("bowl".to_string(), vec![], vec![]));
+        let apple = Mixture::Ingredient(Ingredient("apple".to_string(), vec![], vec![]));
+        let pear = Mixture::Ingredient(Ingredient("pear".to_string(), vec![], vec![]));
+
+        let mut registry = Registry::new();
+        assert!(!registry.is_occupied(&bowl));
+        assert_eq!(Err(VesselError::Empty{index: 0, vessel: "bowl".to_string()}), registry.empty(0, &bowl));
+
+        registry.fill(1, &bowl, &apple).expect("Unable to fill");
+        assert!(registry.is_occupied(&bowl));
+        assert_eq!(Err(VesselError::Occupied{index: 2, vessel: "bowl".to_string()}), registry.fill(2, &bowl, &pear));
+
+        registry.refill(3, &bowl, &pear);
+        registry.configure(&bowl, "chilled");
+        assert_eq!(Ok(pear.clone()), registry.empty(4, &bowl));
+
+        let state = registry.get("bowl").expect("Unknown vessel");
+        assert_eq!(None, state.contents());
+        assert_eq!(&["chilled".to_string()], state.settings());
+        assert_eq!(&[(1, Some(apple)), (3, Some(pear)), (4, None)], state.history());
+    }
+}
@@ -0,0 +1,276 @@
+use failure::Error;
+
+use parallel::axis::Axes;
+use parallel::flow::{Flow, Variant};
+use parallel::names::VariantNames;
+use types::quantity::Magnitude;
+use parser::Parsers;
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum HeaderError {
+    #[fail(display = "unknown header field: {}", key)]
+    UnknownField {
+        key: String,
+    },
+
+    #[fail(display = "header field specified more than once: {}", key)]
+    DuplicateField {
+        key: String,
+    },
+
+    #[fail(display = "invalid value for header field {}: {}", key, value)]
+    InvalidValue {
+        key: String,
+        value: String,
+    },
+}
+
+/// Descriptive information about a recipe, given before its flow.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Header {
+    pub title: Option<String>,
+    pub yields: Option<String>,
+    pub servings: Option<Magnitude>,
+    pub author: Option<String>,
+    pub source: Option<String>,
+    pub tags: Vec<String>,
+    pub variants: VariantNames,
+    pub axes: Axes,
+}
+
+impl Header {
+    pub fn new() -> Self {
+        Header::default()
+    }
+
+    /// Sets a single header field from its source representation, e.g. `servings` and `4-6`.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let duplicate = || HeaderError::DuplicateField{key: key.to_string()};
+
+        match key.to_lowercase().as_str() {
+            "title" => Header::set_once(&mut self.title, value.to_string()).ok_or_else(duplicate)?,
+            "yield" => Header::set_once(&mut self.yields, value.to_string()).ok_or_else(duplicate)?,
+            "author" => Header::set_once(&mut self.author, value.to_string()).ok_or_else(duplicate)?,
+            "source" => Header::set_once(&mut self.source, value.to_string()).ok_or_else(duplicate)?,
+            "servings" => {
+                let servings = match Parsers::magnitude(value.trim()) {
+                    ::nom::IResult::Done("", m) => m,
+                    _ => Err(HeaderError::InvalidValue{key: key.to_string(), value: value.to_string()})?,
+                };
+                Header::set_once(&mut self.servings, servings).ok_or_else(duplicate)?
+            },
+            "tags" => {
+                if !self.tags.is_empty() {
+                    Err(duplicate())?;
+                }
+
+                self.tags = value.split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            },
+            "variants" => {
+                if !self.variants.is_empty() {
+                    Err(duplicate())?;
+                }
+
+                // Names are given slots in the order they are declared.
+                for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    self.variants.declare(name)
+                        .map_err(|_| HeaderError::InvalidValue{key: key.to_string(), value: value.to_string()})?;
+                }
+            },
+            "axis" => {
+                // Each axis is given on its own line, e.g. `@axis: diet: vegan, omnivore`.
+                let invalid = || HeaderError::InvalidValue{key: key.to_string(), value: value.to_string()};
+                let (axis, choices) = value.split_once(':').ok_or_else(invalid)?;
+
+                let mut names = VariantNames::new();
+                for name in choices.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    names.declare(name).map_err(|_| invalid())?;
+                }
+
+                self.axes.declare(axis.trim(), names).map_err(|_| invalid())?;
+            },
+            _ => Err(HeaderError::UnknownField{key: key.to_string()})?,
+        };
+
+        Ok(())
+    }
+
+    fn set_once<T>(field: &mut Option<T>, value: T) -> Option<()> {
+        match *field {
+            Some(_) => None,
+            None => {
+                *field = Some(value);
+                Some(())
+            },
+        }
+    }
+}
+
+/// A complete recipe document: its header, the flow of all its variants, and any trailing notes.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Recipe<'a> {
+    header: Header,
+    flow: Flow<'a>,
+    notes: Vec<String>,
+}
+
+impl<'a> Recipe<'a> {
+    pub fn new(header: Header, flow: Flow<'a>, notes: Vec<String>) -> Self {
+        Recipe { header, flow, notes }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn flow(&self) -> &Flow<'a> {
+        &self.flow
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    /// Produces every distinct walk through the flow, labelled by the names of the slots and axis choices that select it.
+    /// Where every axis in the flow is declared, each of their declared choices is tried.
+    pub fn variants(&self) -> Vec<(Vec<String>, Variant<'_>)> {
+        let assignments = match self.flow.axes().keys().all(|axis| self.header.axes.choices(axis).is_some()) {
+            true => self.header.axes.assignments(),
+            false => self.flow.assignments(),
+        };
+
+        self.flow.variants_with(assignments)
+            .expect("declared assignments have a choice for every axis in the flow")
+            .map(|variant| {
+                let mut labels = self.header.variants.label(&variant.slot_stack);
+                labels.extend(self.header.axes.label(&variant.assignment));
+                (labels, variant)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Header, HeaderError};
+
+    use num_rational::Ratio;
+
+    use parallel::axis::Axes;
+    use parallel::names::VariantNames;
+    use parser::Parsers;
+    use token::Token;
+    use types::quantity::Magnitude;
+
+    fn names(names: &[&str]) -> VariantNames {
+        let mut variants = VariantNames::new();
+        for name in names {
+            variants.declare(*name).expect("Unable to declare");
+        }
+        variants
+    }
+
+    fn axes(axes: &[(&str, &[&str])]) -> Axes {
+        let mut declared = Axes::new();
+        for &(axis, choices) in axes {
+            declared.declare(axis, names(choices)).expect("Unable to declare");
+        }
+        declared
+    }
+
+    #[test]
+    fn test_set_field() {
+        let inputs_and_expected = vec![
+            (vec![("title", "Pancakes")], Some(Header { title: Some("Pancakes".to_string()), ..Header::new() })),
+            (vec![("Servings", "4-6")], Some(Header { servings: Some(Magnitude::range(Ratio::from_integer(4), Ratio::from_integer(6))), ..Header::new() })),
+            (vec![("tags", "breakfast, sweet,")], Some(Header { tags: vec!["breakfast".to_string(), "sweet".to_string()], ..Header::new() })),
+            (vec![("yield", "12 pancakes"), ("author", "Mark"), ("source", "Grandma")], Some(Header {
+                yields: Some("12 pancakes".to_string()),
+                author: Some("Mark".to_string()),
+                source: Some("Grandma".to_string()),
+                ..Header::new()
+            })),
+            (vec![("title", "Pancakes"), ("title", "Waffles")], None),
+            (vec![("variants", "vegan, grill")], Some(Header { variants: names(&["vegan", "grill"]), ..Header::new() })),
+            (vec![("axis", "diet: vegan, omnivore"), ("axis", "method : grill")], Some(Header { axes: axes(&[("diet", &["vegan", "omnivore"]), ("method", &["grill"])]), ..Header::new() })),
+            (vec![("axis", "diet: vegan"), ("axis", "diet: keto")], None),
+            (vec![("axis", "diet")], None),
+            (vec![("axis", "diet:")], None),
+            (vec![("variants", "vegan, vegan")], None),
+            (vec![("variants", "no dairy")], None),
+            (vec![("variants", "vegan"), ("variants", "grill")], None),
+            (vec![("servings", "lots")], None),
+            (vec![("calories", "500")], None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut header = Header::new();
+            let produced = input.into_iter()
+                .map(|(k, v)| header.set_field(k, v))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .map(|_| header.clone());
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        let produced = Header::new().set_field("calories", "500")
+            .unwrap_err()
+            .downcast::<HeaderError>()
+            .expect("Unexpected error type");
+        let expected = HeaderError::UnknownField{key: "calories".to_string()};
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_variants() {
+        let recipe = Parsers::parse_recipe("@variants: vegan, grill\n* tofu [ = grill #grill | = bake #!grill ] [ * butter #!vegan ]")
+            .expect("Unable to parse recipe");
+
+        let produced: Vec<_> = recipe.variants().into_iter()
+            .map(|(labels, variant)| (labels, variant.walk.len()))
+            .collect();
+
+        // Both splits are at the outermost level, so one slot selects from each of them.
+        let expected = vec![(vec!["vegan".to_string()], 2), (vec!["2".to_string()], 3), (vec!["grill".to_string()], 3)];
+        assert_eq!(expected, produced);
+
+        let (_, ref variant) = recipe.variants()[0];
+        assert_eq!(&Token::Verb("bake".to_string()), variant.walk[1]);
+    }
+
+    #[test]
+    fn test_axes() {
+        let input = "@axis: diet: vegan, omnivore\n@axis: method: grill, oven\n\
+            * tofu [ * bacon #diet: omnivore ] [ = grill #method: grill | = bake #method: oven and not diet: vegan ]";
+        let recipe = Parsers::parse_recipe(input).expect("Unable to parse recipe");
+
+        let produced: Vec<_> = recipe.variants().into_iter()
+            .map(|(labels, variant)| (labels, variant.walk.len()))
+            .collect();
+
+        // Bacon is only added for omnivores, and baking is only done for those who are not vegan.
+        let label = |d: &str, m: &str| vec![format!("diet: {}", d), format!("method: {}", m)];
+        let expected = vec![
+            (label("vegan", "oven"), 1),
+            (label("vegan", "grill"), 2),
+            (label("omnivore", "oven"), 3),
+            (label("omnivore", "grill"), 3),
+        ];
+        assert_eq!(expected, produced);
+
+        let assignment = recipe.header().axes.assignment(&[("diet", "omnivore"), ("method", "oven")]).expect("Unable to assign");
+        let walks: Vec<_> = recipe.flow().iter_walks_with(vec![], &assignment).expect("Unable to iterate walks").collect();
+        assert_eq!(vec![vec![
+            &Token::Ingredient("tofu".to_string()),
+            &Token::Ingredient("bacon".to_string()),
+            &Token::Verb("bake".to_string()),
+        ]], walks);
+    }
+}
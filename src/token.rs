@@ -16,6 +16,7 @@ pub enum Token {
     Place,
     Remove,
     Configure(String),
+    /// Pours the contents of one filled vessel into another, e.g. "+meld: fold in".
     Meld(String),
     Discard,
     Empty,
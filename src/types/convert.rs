@@ -0,0 +1,337 @@
+use failure::Error;
+use num_rational::Ratio;
+use num_traits::{CheckedDiv, CheckedMul};
+
+use super::quantity::{Quantity, Magnitude};
+use super::unit::{Amount, Dimension, Unit};
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum ConversionError {
+    #[fail(display = "cannot convert from {} to {} without a density", from, to)]
+    DensityRequired {
+        from: Dimension,
+        to: Dimension,
+    },
+
+    #[fail(display = "cannot convert from {} to {}", from, to)]
+    Incompatible {
+        from: Dimension,
+        to: Dimension,
+    },
+
+    #[fail(display = "density must be positive and nonzero")]
+    InvalidDensity,
+
+    #[fail(display = "density cannot be calculated from a range of quantities")]
+    InexactDensity,
+
+    #[fail(display = "amount is too large or too precise to convert exactly")]
+    Overflow,
+
+    #[fail(display = "cannot round to a multiple of 1/{}", denominator)]
+    InvalidRounding {
+        denominator: i64,
+    },
+}
+
+/// A mass per unit volume, used to bridge conversions between mass and volume units.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub struct Density(Amount);
+
+impl Density {
+    pub fn new(grams_per_milliliter: Amount) -> Result<Self, Error> {
+        if *grams_per_milliliter.numer() <= 0 {
+            Err(ConversionError::InvalidDensity)?;
+        }
+
+        Ok(Density(grams_per_milliliter))
+    }
+
+    /// Calculates a density from a known mass that occupies a known volume, e.g. 120 g per 1 cup.
+    pub fn from_quantities(mass: &Quantity, volume: &Quantity) -> Result<Self, Error> {
+        if mass.dimension() != Dimension::Mass || volume.dimension() != Dimension::Volume {
+            Err(ConversionError::Incompatible{from: mass.dimension(), to: volume.dimension()})?;
+        }
+
+        let (grams, milliliters) = match (mass.magnitude(), volume.magnitude()) {
+            (Magnitude::Exact(m), Magnitude::Exact(v)) => (
+                mass.unit().base_amount(m).ok_or(ConversionError::Overflow)?,
+                volume.unit().base_amount(v).ok_or(ConversionError::Overflow)?,
+            ),
+            _ => Err(ConversionError::InexactDensity)?,
+        };
+
+        if *milliliters.numer() <= 0 {
+            Err(ConversionError::InvalidDensity)?;
+        }
+
+        Density::new(grams.checked_div(&milliliters).ok_or(ConversionError::Overflow)?)
+    }
+
+    pub fn grams_per_milliliter(&self) -> Amount {
+        self.0
+    }
+}
+
+/// A family of units that a recipe can be presented in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum MeasurementSystem {
+    Metric,
+    UsCustomary,
+    Imperial,
+}
+
+impl MeasurementSystem {
+    /// The units used by this system for a given dimension, ordered from smallest to largest.
+    pub fn units(&self, dimension: Dimension) -> Vec<Unit> {
+        match (*self, dimension) {
+            (_, Dimension::Count) => vec![Unit::Each],
+            (_, Dimension::Time) => vec![Unit::Second, Unit::Minute, Unit::Hour],
+
+            (MeasurementSystem::Metric, Dimension::Mass) => vec![Unit::Milligram, Unit::Gram, Unit::Kilogram],
+            (MeasurementSystem::Metric, Dimension::Volume) => vec![Unit::Milliliter, Unit::Liter],
+            (MeasurementSystem::Metric, Dimension::Length) => vec![Unit::Millimeter, Unit::Centimeter, Unit::Meter],
+            (MeasurementSystem::Metric, Dimension::Temperature) => vec![Unit::Celsius],
+
+            (MeasurementSystem::UsCustomary, Dimension::Mass) => vec![Unit::Ounce, Unit::Pound],
+            (MeasurementSystem::UsCustomary, Dimension::Volume) => vec![
+                Unit::Teaspoon, Unit::Tablespoon, Unit::FluidOunce, Unit::Cup, Unit::Pint, Unit::Quart, Unit::Gallon,
+            ],
+            (MeasurementSystem::UsCustomary, Dimension::Length) => vec![Unit::Inch],
+            (MeasurementSystem::UsCustomary, Dimension::Temperature) => vec![Unit::Fahrenheit],
+
+            (MeasurementSystem::Imperial, Dimension::Mass) => vec![Unit::Ounce, Unit::Pound],
+            (MeasurementSystem::Imperial, Dimension::Volume) => vec![
+                Unit::ImperialTeaspoon, Unit::ImperialTablespoon, Unit::ImperialFluidOunce, Unit::ImperialCup,
+                Unit::ImperialPint, Unit::ImperialQuart, Unit::ImperialGallon,
+            ],
+            (MeasurementSystem::Imperial, Dimension::Length) => vec![Unit::Inch],
+            // British usage pairs imperial volumes with Celsius oven temperatures.
+            (MeasurementSystem::Imperial, Dimension::Temperature) => vec![Unit::Celsius],
+        }
+    }
+
+    pub fn contains(&self, unit: Unit) -> bool {
+        self.units(unit.dimension()).contains(&unit)
+    }
+}
+
+impl Quantity {
+    /// Converts this quantity into another unit of the same dimension.
+    pub fn convert(&self, unit: Unit) -> Result<Quantity, Error> {
+        let (from, to) = (self.dimension(), unit.dimension());
+
+        if from != to {
+            match (from, to) {
+                (Dimension::Mass, Dimension::Volume) | (Dimension::Volume, Dimension::Mass) => {
+                    Err(ConversionError::DensityRequired{from, to})?;
+                },
+                _ => {
+                    Err(ConversionError::Incompatible{from, to})?;
+                },
+            }
+        }
+
+        let from_unit = self.unit();
+        Ok(self.try_map(|a| unit.unit_amount(from_unit.base_amount(a)?), unit).ok_or(ConversionError::Overflow)?)
+    }
+
+    /// Converts this quantity into another unit, using a density to bridge between mass and volume if needed.
+    pub fn convert_with_density(&self, unit: Unit, density: Density) -> Result<Quantity, Error> {
+        let from_unit = self.unit();
+        let gpm = density.grams_per_milliliter();
+
+        let converted = match (self.dimension(), unit.dimension()) {
+            (Dimension::Mass, Dimension::Volume) => self.try_map(|a| unit.unit_amount(from_unit.base_amount(a)?.checked_div(&gpm)?), unit),
+            (Dimension::Volume, Dimension::Mass) => self.try_map(|a| unit.unit_amount(from_unit.base_amount(a)?.checked_mul(&gpm)?), unit),
+            _ => return self.convert(unit),
+        };
+
+        Ok(converted.ok_or(ConversionError::Overflow)?)
+    }
+
+    /// Re-expresses this quantity in the given measurement system.
+    /// Quantities already in a unit of that system are left alone; otherwise, the largest unit that
+    /// yields an amount of at least one is chosen, falling back to the smallest unit.
+    pub fn in_system(&self, system: MeasurementSystem) -> Result<Quantity, Error> {
+        if system.contains(self.unit()) {
+            return Ok(*self);
+        }
+
+        let from_unit = self.unit();
+        let base_amount = from_unit.base_amount(self.magnitude().low()).ok_or(ConversionError::Overflow)?;
+        let one = Ratio::from_integer(1);
+
+        // For ranges, the unit is chosen based on the low end.
+        let units = system.units(self.dimension());
+        let unit = units.iter()
+            .rev()
+            .find(|u| u.unit_amount(base_amount) >= Some(one))
+            .or_else(|| units.first())
+            .cloned()
+            .unwrap_or(from_unit);
+
+        self.convert(unit)
+    }
+
+    /// Rounds the amount of this quantity to the nearest multiple of `1 / denominator`.
+    /// This is useful for presenting converted quantities, which rarely land on tidy values.
+    /// If rounding changes the amount, the result is marked as approximate. The denominator must be positive.
+    pub fn round_to(&self, denominator: i64) -> Result<Quantity, Error> {
+        if denominator <= 0 {
+            Err(ConversionError::InvalidRounding{denominator})?;
+        }
+
+        let denominator = Ratio::from_integer(denominator);
+        let rounded = self.try_map(|a| Some(a.checked_mul(&denominator)?.round() / denominator), self.unit())
+            .ok_or(ConversionError::Overflow)?;
+
+        match rounded == *self {
+            true => Ok(rounded),
+            false => Ok(rounded.into_approximate()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConversionError, Density, MeasurementSystem};
+    use super::super::quantity::Quantity;
+    use super::super::unit::{Dimension, Unit, parse_amount};
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_convert() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Pound), Unit::Ounce), Some(Quantity::whole(16, Unit::Ounce))),
+            ((Quantity::whole(2, Unit::Kilogram), Unit::Gram), Some(Quantity::whole(2000, Unit::Gram))),
+            ((Quantity::whole(1, Unit::Cup), Unit::Tablespoon), Some(Quantity::whole(16, Unit::Tablespoon))),
+            ((Quantity::whole(1, Unit::Gallon), Unit::Pint), Some(Quantity::whole(8, Unit::Pint))),
+            ((Quantity::whole(1, Unit::ImperialPint), Unit::ImperialFluidOunce), Some(Quantity::whole(20, Unit::ImperialFluidOunce))),
+            ((Quantity::whole(1, Unit::ImperialGallon), Unit::Liter), Some(Quantity::new(Ratio::new(454_609, 100_000), Unit::Liter))),
+            ((Quantity::whole(180, Unit::Celsius), Unit::Fahrenheit), Some(Quantity::whole(356, Unit::Fahrenheit))),
+            ((Quantity::whole(212, Unit::Fahrenheit), Unit::Kelvin), Some(Quantity::new(Ratio::new(37_315, 100), Unit::Kelvin))),
+            ((Quantity::whole(1, Unit::Cup), Unit::Gram), None),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Kilogram), Unit::Gram),
+                Some(Quantity::range(Ratio::from_integer(1000), Ratio::from_integer(2000), Unit::Gram))),
+            ((Quantity::whole(1, Unit::Hour), Unit::Celsius), None),
+        ];
+
+        for ((quantity, unit), expected) in inputs_and_expected {
+            let produced = quantity.convert(unit).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_convert_errors() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Cup), Unit::Gram),
+                ConversionError::DensityRequired{from: Dimension::Volume, to: Dimension::Mass}),
+            ((Quantity::whole(1, Unit::Ounce), Unit::Milliliter),
+                ConversionError::DensityRequired{from: Dimension::Mass, to: Dimension::Volume}),
+            ((Quantity::whole(1, Unit::Hour), Unit::Celsius),
+                ConversionError::Incompatible{from: Dimension::Time, to: Dimension::Temperature}),
+            ((Quantity::new(parse_amount("0.123456789012345").unwrap(), Unit::Cup), Unit::Milliliter), ConversionError::Overflow),
+            ((Quantity::whole(123_456_789_012, Unit::Gallon), Unit::Milliliter), ConversionError::Overflow),
+        ];
+
+        for ((quantity, unit), expected) in inputs_and_expected {
+            let produced = quantity.convert(unit)
+                .unwrap_err()
+                .downcast::<ConversionError>()
+                .expect("Unexpected error type");
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_convert_with_density() {
+        let water = Density::new(Ratio::from_integer(1)).unwrap();
+        let flour = Density::from_quantities(&Quantity::whole(120, Unit::Gram), &Quantity::whole(1, Unit::Cup)).unwrap();
+
+        let inputs_and_expected = vec![
+            ((Quantity::whole(250, Unit::Milliliter), Unit::Gram, water), Some(Quantity::whole(250, Unit::Gram))),
+            ((Quantity::whole(1, Unit::Kilogram), Unit::Liter, water), Some(Quantity::whole(1, Unit::Liter))),
+            ((Quantity::whole(2, Unit::Cup), Unit::Gram, flour), Some(Quantity::whole(240, Unit::Gram))),
+            ((Quantity::whole(60, Unit::Gram), Unit::Cup, flour), Some(Quantity::new(Ratio::new(1, 2), Unit::Cup))),
+            ((Quantity::whole(1, Unit::Pound), Unit::Ounce, flour), Some(Quantity::whole(16, Unit::Ounce))),
+            ((Quantity::whole(1, Unit::Cup), Unit::Minute, flour), None),
+        ];
+
+        for ((quantity, unit, density), expected) in inputs_and_expected {
+            let produced = quantity.convert_with_density(unit, density).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_density_new() {
+        let inputs_and_expected = vec![
+            (Ratio::new(1, 2), true),
+            (Ratio::from_integer(0), false),
+            (Ratio::from_integer(-1), false),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Density::new(input).is_ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_in_system() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Cup), MeasurementSystem::UsCustomary), Quantity::whole(1, Unit::Cup)),
+            ((Quantity::whole(8, Unit::FluidOunce), MeasurementSystem::UsCustomary), Quantity::whole(8, Unit::FluidOunce)),
+            ((Quantity::whole(2, Unit::Pint), MeasurementSystem::UsCustomary), Quantity::whole(2, Unit::Pint)),
+            ((Quantity::whole(100, Unit::Milliliter), MeasurementSystem::UsCustomary), Quantity::new(Ratio::new(1_600_000_000, 473_176_473), Unit::FluidOunce)),
+            ((Quantity::whole(1, Unit::ImperialQuart), MeasurementSystem::Imperial), Quantity::whole(1, Unit::ImperialQuart)),
+            ((Quantity::whole(15, Unit::Milliliter), MeasurementSystem::Metric), Quantity::whole(15, Unit::Milliliter)),
+            ((Quantity::whole(1, Unit::Pound), MeasurementSystem::Metric), Quantity::new(Ratio::new(45_359_237, 100_000), Unit::Gram)),
+            ((Quantity::whole(2, Unit::Kilogram), MeasurementSystem::UsCustomary), Quantity::new(Ratio::new(200_000_000, 45_359_237), Unit::Pound)),
+            ((Quantity::whole(1500, Unit::Milligram), MeasurementSystem::UsCustomary), Quantity::new(Ratio::new(2_400_000, 45_359_237), Unit::Ounce)),
+            ((Quantity::whole(2, Unit::Quart), MeasurementSystem::Metric), Quantity::new(Ratio::new(473_176_473, 250_000_000), Unit::Liter)),
+            ((Quantity::whole(200, Unit::Celsius), MeasurementSystem::UsCustomary), Quantity::whole(392, Unit::Fahrenheit)),
+            ((Quantity::whole(392, Unit::Fahrenheit), MeasurementSystem::Imperial), Quantity::whole(200, Unit::Celsius)),
+            ((Quantity::whole(3, Unit::Each), MeasurementSystem::Metric), Quantity::whole(3, Unit::Each)),
+            ((Quantity::whole(200, Unit::Celsius).into_approximate(), MeasurementSystem::UsCustomary), Quantity::whole(392, Unit::Fahrenheit).into_approximate()),
+            ((Quantity::range(Ratio::from_integer(500), Ratio::from_integer(1500), Unit::Gram), MeasurementSystem::Metric),
+                Quantity::range(Ratio::from_integer(500), Ratio::from_integer(1500), Unit::Gram)),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Kilogram), MeasurementSystem::UsCustomary),
+                Quantity::range(Ratio::new(100_000_000, 45_359_237), Ratio::new(200_000_000, 45_359_237), Unit::Pound)),
+        ];
+
+        for ((quantity, system), expected) in inputs_and_expected {
+            let produced = quantity.in_system(system).expect("Unable to convert");
+            assert_eq!(expected, produced);
+        }
+
+        // Going out to another system and back again gives the same amount.
+        let quantity = Quantity::whole(8, Unit::FluidOunce);
+        let produced = quantity.in_system(MeasurementSystem::Metric).and_then(|q| q.in_system(MeasurementSystem::UsCustomary))
+            .expect("Unable to convert");
+        assert_eq!(Quantity::whole(1, Unit::Cup), produced);
+        assert_eq!(quantity, produced.convert(Unit::FluidOunce).expect("Unable to convert"));
+    }
+
+    #[test]
+    fn test_round_to() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Cup).convert(Unit::Milliliter).unwrap(), 1), Some(Quantity::whole(237, Unit::Milliliter).into_approximate())),
+            ((Quantity::whole(1, Unit::Pound).convert(Unit::Gram).unwrap(), 1), Some(Quantity::whole(454, Unit::Gram).into_approximate())),
+            ((Quantity::whole(100, Unit::Gram).convert(Unit::Ounce).unwrap(), 4), Some(Quantity::new(Ratio::new(7, 2), Unit::Ounce).into_approximate())),
+            ((Quantity::whole(2, Unit::Kilogram).convert(Unit::Gram).unwrap(), 1), Some(Quantity::whole(2000, Unit::Gram))),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Cup).convert(Unit::Milliliter).unwrap(), 1),
+                Some(Quantity::range(Ratio::from_integer(237), Ratio::from_integer(473), Unit::Milliliter).into_approximate())),
+            ((Quantity::whole(1, Unit::Cup), 0), None),
+            ((Quantity::whole(1, Unit::Cup), -2), None),
+        ];
+
+        for ((quantity, denominator), expected) in inputs_and_expected {
+            let produced = quantity.round_to(denominator).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use failure::Error;
+use num_rational::Ratio;
+use toml;
+
+use super::convert::Density;
+use super::quantity::Quantity;
+use super::unit::{Unit, parse_amount};
+
+/// Built-in densities for common staples, in grams per US cup.
+const BUILTIN_GRAMS_PER_CUP: &[(&str, i64)] = &[
+    ("all-purpose flour", 120),
+    ("flour", 120),
+    ("bread flour", 120),
+    ("cake flour", 113),
+    ("whole wheat flour", 113),
+    ("cornmeal", 138),
+    ("rolled oats", 89),
+    ("cocoa powder", 84),
+    ("granulated sugar", 198),
+    ("sugar", 198),
+    ("brown sugar", 213),
+    ("powdered sugar", 113),
+    ("honey", 336),
+    ("maple syrup", 312),
+    ("butter", 227),
+    ("milk", 227),
+    ("heavy cream", 227),
+    ("yogurt", 227),
+    ("vegetable oil", 198),
+    ("olive oil", 200),
+    ("salt", 288),
+    ("white rice", 198),
+];
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum DensityTableError {
+    #[fail(display = "no density known for ingredient: {}", name)]
+    UnknownIngredient {
+        name: String,
+    },
+
+    #[fail(display = "malformed density entry on line {}: {}", line, content)]
+    Malformed {
+        line: usize,
+        content: String,
+    },
+
+    #[fail(display = "invalid density for ingredient {}: {}", name, value)]
+    InvalidEntry {
+        name: String,
+        value: String,
+    },
+
+    #[fail(display = "unsupported density table format: {}", path)]
+    UnsupportedFormat {
+        path: String,
+    },
+}
+
+/// A registry of ingredient densities, keyed by ingredient name.
+/// Names are matched case-insensitively, and runs of whitespace are treated as a single space.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct DensityTable(HashMap<String, Density>);
+
+impl DensityTable {
+    pub fn new() -> Self {
+        DensityTable(hashmap![])
+    }
+
+    /// Creates a table pre-populated with densities for common staples.
+    pub fn builtin() -> Self {
+        let cup = Quantity::whole(1, Unit::Cup);
+        let mut table = DensityTable::new();
+
+        for &(name, grams) in BUILTIN_GRAMS_PER_CUP {
+            let density = Density::from_quantities(&Quantity::whole(grams, Unit::Gram), &cup)
+                .expect("built-in densities are valid");
+            table.insert(name, density);
+        }
+
+        table.insert("water", Density::new(Ratio::from_integer(1)).expect("built-in densities are valid"));
+
+        table
+    }
+
+    fn normalize_name(name: &str) -> String {
+        name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+
+    /// Adds or replaces the density for an ingredient.
+    pub fn insert(&mut self, name: &str, density: Density) {
+        self.0.insert(DensityTable::normalize_name(name), density);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Density> {
+        self.0.get(&DensityTable::normalize_name(name)).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds all entries from another table, replacing any existing entries with the same name.
+    pub fn extend(&mut self, other: DensityTable) {
+        self.0.extend(other.0);
+    }
+
+    /// Converts a quantity of an ingredient into another unit, using the ingredient's density if needed.
+    pub fn convert(&self, name: &str, quantity: &Quantity, unit: Unit) -> Result<Quantity, Error> {
+        if quantity.dimension() == unit.dimension() {
+            return quantity.convert(unit);
+        }
+
+        let density = self.get(name).ok_or_else(|| DensityTableError::UnknownIngredient{name: name.to_string()})?;
+        quantity.convert_with_density(unit, density)
+    }
+
+    /// Parses CSV entries of the form `name, grams per milliliter`.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse_csv(contents: &str) -> Result<Self, Error> {
+        let mut table = DensityTable::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || DensityTableError::Malformed{line: index + 1, content: line.to_string()};
+
+            let sep_pos = line.rfind(',').ok_or_else(malformed)?;
+            let name = line[..sep_pos].trim().trim_matches('"');
+            let value = line[sep_pos + 1..].trim().trim_matches('"');
+
+            if name.is_empty() {
+                Err(malformed())?;
+            }
+
+            let amount = parse_amount(value).ok_or_else(malformed)?;
+            let density = Density::new(amount).map_err(|_| malformed())?;
+
+            table.insert(name, density);
+        }
+
+        Ok(table)
+    }
+
+    /// Parses a TOML document whose keys are ingredient names and whose values are grams per milliliter.
+    /// Entries may also be grouped into tables (e.g. under `[densities]`), and values may be strings to allow fractions.
+    pub fn parse_toml(contents: &str) -> Result<Self, Error> {
+        let document: toml::Value = contents.parse()?;
+        let mut table = DensityTable::new();
+
+        DensityTable::collect_toml(&document, &mut table)?;
+
+        Ok(table)
+    }
+
+    fn collect_toml(value: &toml::Value, table: &mut DensityTable) -> Result<(), Error> {
+        let entries = match *value {
+            toml::Value::Table(ref entries) => entries,
+            _ => return Ok(()),
+        };
+
+        for (name, value) in entries {
+            let invalid = || DensityTableError::InvalidEntry{name: name.to_string(), value: value.to_string()};
+
+            let amount = match *value {
+                toml::Value::Table(_) => {
+                    DensityTable::collect_toml(value, table)?;
+                    continue;
+                },
+                toml::Value::Integer(i) => Some(Ratio::from_integer(i)),
+                toml::Value::Float(f) => parse_amount(&f.to_string()),
+                toml::Value::String(ref s) => parse_amount(s),
+                _ => None,
+            };
+
+            let density = amount.ok_or_else(invalid).and_then(|a| Density::new(a).map_err(|_| invalid()))?;
+
+            table.insert(name, density);
+        }
+
+        Ok(())
+    }
+
+    /// Loads a table from a local file, choosing the format based on its `.csv` or `.toml` extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => DensityTable::parse_csv(&contents),
+            Some("toml") => DensityTable::parse_toml(&contents),
+            _ => Err(DensityTableError::UnsupportedFormat{path: path.display().to_string()})?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DensityTable, DensityTableError};
+    use super::super::convert::Density;
+    use super::super::quantity::Quantity;
+    use super::super::unit::Unit;
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_builtin() {
+        let table = DensityTable::builtin();
+
+        let inputs_and_expected = vec![
+            (("flour", Quantity::whole(1, Unit::Cup), Unit::Gram), Some(Quantity::whole(120, Unit::Gram))),
+            (("All-Purpose   Flour", Quantity::whole(2, Unit::Cup), Unit::Gram), Some(Quantity::whole(240, Unit::Gram))),
+            (("sugar", Quantity::whole(99, Unit::Gram), Unit::Cup), Some(Quantity::new(Ratio::new(1, 2), Unit::Cup))),
+            (("water", Quantity::whole(1, Unit::Liter), Unit::Kilogram), Some(Quantity::whole(1, Unit::Kilogram))),
+            (("unobtainium", Quantity::whole(1, Unit::Cup), Unit::Gram), None),
+            (("unobtainium", Quantity::whole(1, Unit::Cup), Unit::Milliliter), Some(Quantity::new(Ratio::new(473_176_473, 2_000_000), Unit::Milliliter))),
+        ];
+
+        for ((name, quantity, unit), expected) in inputs_and_expected {
+            let produced = table.convert(name, &quantity, unit).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_unknown_ingredient() {
+        let produced = DensityTable::new().convert("flour", &Quantity::whole(1, Unit::Cup), Unit::Gram)
+            .unwrap_err()
+            .downcast::<DensityTableError>()
+            .expect("Unexpected error type");
+        let expected = DensityTableError::UnknownIngredient{name: "flour".to_string()};
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let inputs_and_expected = vec![
+            ("", Some(vec![])),
+            ("# name, g/ml\nalmond flour, 0.41\n\nghee, 9/10\n", Some(vec![
+                ("almond flour", Ratio::new(41, 100)),
+                ("ghee", Ratio::new(9, 10)),
+            ])),
+            ("almond flour 0.41", None),
+            (", 0.41", None),
+            ("ghee, lots", None),
+            ("ghee, 0", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = DensityTable::parse_csv(input).ok();
+            let expected = expected.map(|entries| {
+                let mut table = DensityTable::new();
+                for (name, amount) in entries {
+                    table.insert(name, Density::new(amount).unwrap());
+                }
+                table
+            });
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let inputs_and_expected = vec![
+            ("[densities]\n\"almond flour\" = 0.41\nghee = \"9/10\"\n", Some(vec![
+                ("almond flour", Ratio::new(41, 100)),
+                ("ghee", Ratio::new(9, 10)),
+            ])),
+            ("[oils]\n\"olive oil\" = 0.91\n\n[fats]\nghee = 1\n", Some(vec![
+                ("olive oil", Ratio::new(91, 100)),
+                ("ghee", Ratio::from_integer(1)),
+            ])),
+            ("ghee: 0.9", None),
+            ("ghee = 0.9 = 0.8", None),
+            ("ghee = \"lots\"", None),
+            ("ghee = 0", None),
+            ("ghee = [0.9]", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = DensityTable::parse_toml(input).ok();
+            let expected = expected.map(|entries| {
+                let mut table = DensityTable::new();
+                for (name, amount) in entries {
+                    table.insert(name, Density::new(amount).unwrap());
+                }
+                table
+            });
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut table = DensityTable::builtin();
+        let count = table.len();
+
+        table.extend(DensityTable::parse_csv("flour, 1/2\nghee, 9/10").unwrap());
+
+        assert_eq!(count + 1, table.len());
+        assert_eq!(Some(Density::new(Ratio::new(1, 2)).unwrap()), table.get("FLOUR"));
+    }
+}
@@ -0,0 +1,233 @@
+pub mod unit;
+pub mod quantity;
+pub mod convert;
+pub mod density;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use failure::Error;
+
+use self::unit::Amount;
+pub use self::quantity::Quantity;
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum PortionError {
+    #[fail(display = "fractional portions must lie strictly between 0 and 1, with a denominator of at most 255; found: {}/{}", numer, denom)]
+    InvalidFraction {
+        numer: u64,
+        denom: u64,
+    },
+
+    #[fail(display = "relative portions cannot be totalled; found: {}/{}", numer, denom)]
+    Relative {
+        numer: u8,
+        denom: u8,
+    },
+}
+
+/// An amount of an ingredient or mixture.
+/// Unquantified amounts, such as "to taste" or "a pinch", are kept as pseudo portions.
+#[derive(Clone, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum Portion {
+    Pseudo(String),
+    Quantity(Quantity),
+    Fraction(u8, u8),
+}
+
+impl Portion {
+    /// Creates a fractional portion in lowest terms, which must lie strictly between 0 and 1.
+    pub fn fraction(numer: u64, denom: u64) -> Result<Portion, Error> {
+        let invalid = || PortionError::InvalidFraction{numer, denom};
+
+        if numer == 0 || numer >= denom {
+            Err(invalid())?;
+        }
+
+        let divisor = gcd(numer, denom);
+        let (n, d) = (numer / divisor, denom / divisor);
+
+        if d > u64::from(u8::MAX) {
+            Err(invalid())?;
+        }
+
+        Ok(Portion::Fraction(n as u8, d as u8))
+    }
+
+    pub fn is_quantified(&self) -> bool {
+        match *self {
+            Portion::Quantity(_) => true,
+            Portion::Pseudo(_) | Portion::Fraction(..) => false,
+        }
+    }
+
+    /// Scales a portion, e.g. when doubling a recipe.
+    /// Pseudo and fractional portions are relative to the recipe, and are unaffected, as are temperatures.
+    pub fn scale(&self, factor: Amount) -> Portion {
+        match *self {
+            Portion::Quantity(ref q) => Portion::Quantity(*q * factor),
+            _ => self.clone(),
+        }
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+impl fmt::Display for Portion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Portion::Pseudo(ref s) => write!(f, "{}", s),
+            Portion::Quantity(ref q) => write!(f, "{}", q),
+            Portion::Fraction(n, d) => write!(f, "{}/{}", n, d),
+        }
+    }
+}
+
+/// A running total of portions of a single ingredient, as needed for a shopping list.
+/// Quantities are summed, keeping any ranges and estimates, while pseudo portions are collected as notes.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Tally {
+    total: Option<Quantity>,
+    notes: BTreeSet<String>,
+}
+
+impl Tally {
+    pub fn new() -> Self {
+        Tally::default()
+    }
+
+    pub fn add(&mut self, portion: &Portion) -> Result<(), Error> {
+        match *portion {
+            Portion::Quantity(ref q) => {
+                self.total = Quantity::sum(self.total.iter().chain(Some(q)))?;
+            },
+            Portion::Pseudo(ref s) => {
+                self.notes.insert(s.clone());
+            },
+            Portion::Fraction(numer, denom) => {
+                Err(PortionError::Relative{numer, denom})?;
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn total(&self) -> Option<&Quantity> {
+        self.total.as_ref()
+    }
+
+    pub fn notes(&self) -> &BTreeSet<String> {
+        &self.notes
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = self.total.iter().map(|q| q.to_string()).collect();
+        parts.extend(self.notes.iter().cloned());
+
+        write!(f, "{}", parts.join(", plus "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Portion, PortionError, Tally, Quantity};
+    use super::unit::Unit;
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_fraction() {
+        let inputs_and_expected = vec![
+            ((1, 3), Some(Portion::Fraction(1, 3))),
+            ((2, 4), Some(Portion::Fraction(1, 2))),
+            ((6, 9), Some(Portion::Fraction(2, 3))),
+            ((254, 255), Some(Portion::Fraction(254, 255))),
+            ((300, 600), Some(Portion::Fraction(1, 2))),
+            ((1, 256), None),
+            ((0, 3), None),
+            ((3, 3), None),
+            ((4, 3), None),
+            ((0, 0), None),
+        ];
+
+        for ((numer, denom), expected) in inputs_and_expected {
+            let produced = Portion::fraction(numer, denom).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_scale() {
+        let inputs_and_expected = vec![
+            ((Portion::Quantity(Quantity::whole(200, Unit::Gram)), Ratio::from_integer(2)),
+                Portion::Quantity(Quantity::whole(400, Unit::Gram))),
+            ((Portion::Quantity(Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each)), Ratio::new(1, 2)),
+                Portion::Quantity(Quantity::range(Ratio::from_integer(1), Ratio::new(3, 2), Unit::Each))),
+            ((Portion::Pseudo("to taste".to_string()), Ratio::from_integer(2)),
+                Portion::Pseudo("to taste".to_string())),
+            ((Portion::Fraction(1, 3), Ratio::from_integer(2)),
+                Portion::Fraction(1, 3)),
+            ((Portion::Quantity(Quantity::whole(180, Unit::Celsius)), Ratio::from_integer(2)),
+                Portion::Quantity(Quantity::whole(180, Unit::Celsius))),
+        ];
+
+        for ((portion, factor), expected) in inputs_and_expected {
+            let produced = portion.scale(factor);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_tally() {
+        let inputs_and_expected = vec![
+            (vec![], Some("".to_string())),
+            (
+                vec![
+                    Portion::Quantity(Quantity::whole(2, Unit::Each)),
+                    Portion::Quantity(Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Each)),
+                ],
+                Some("3-4".to_string()),
+            ),
+            (
+                vec![
+                    Portion::Quantity(Quantity::whole(1, Unit::Teaspoon)),
+                    Portion::Pseudo("to taste".to_string()),
+                    Portion::Quantity(Quantity::whole(1, Unit::Teaspoon).into_approximate()),
+                    Portion::Pseudo("to taste".to_string()),
+                ],
+                Some("about 2 tsp, plus to taste".to_string()),
+            ),
+            (vec![Portion::Pseudo("a pinch".to_string())], Some("a pinch".to_string())),
+            (vec![Portion::Quantity(Quantity::whole(1, Unit::Cup)), Portion::Quantity(Quantity::whole(1, Unit::Gram))], None),
+            (vec![Portion::Fraction(1, 2)], None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let mut tally = Tally::new();
+            let produced = input.iter()
+                .map(|p| tally.add(p))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .map(|_| tally.to_string());
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_relative_portion() {
+        let produced = Tally::new().add(&Portion::Fraction(1, 3))
+            .unwrap_err()
+            .downcast::<PortionError>()
+            .expect("Unexpected error type");
+        let expected = PortionError::Relative{numer: 1, denom: 3};
+
+        assert_eq!(expected, produced);
+    }
+}
@@ -0,0 +1,475 @@
+use std::fmt;
+use std::cmp::Ordering;
+use std::ops::{Mul, Div};
+
+use failure::Error;
+use num_rational::Ratio;
+
+use super::convert::ConversionError;
+use super::unit::{Amount, Dimension, Unit};
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum QuantityError {
+    #[fail(display = "quantities have different dimensions; left: {}, right: {}", left, right)]
+    DimensionMismatch {
+        left: Dimension,
+        right: Dimension,
+    },
+
+    #[fail(display = "quantities overlap and cannot be ordered; left: {}, right: {}", left, right)]
+    Overlapping {
+        left: Quantity,
+        right: Quantity,
+    },
+
+    #[fail(display = "absolute temperatures cannot be added; left: {}, right: {}", left, right)]
+    AbsoluteTemperatures {
+        left: Quantity,
+        right: Quantity,
+    },
+}
+
+/// The numeric part of a quantity, either a single exact amount or a closed range of amounts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum Magnitude {
+    Exact(Amount),
+    Range(Amount, Amount),
+}
+
+impl Magnitude {
+    /// Creates a range between two amounts, in either order.
+    /// A range whose bounds are equal collapses into an exact amount.
+    pub fn range(a: Amount, b: Amount) -> Self {
+        match a.cmp(&b) {
+            Ordering::Less => Magnitude::Range(a, b),
+            Ordering::Equal => Magnitude::Exact(a),
+            Ordering::Greater => Magnitude::Range(b, a),
+        }
+    }
+
+    pub fn low(&self) -> Amount {
+        match *self {
+            Magnitude::Exact(a) | Magnitude::Range(a, _) => a,
+        }
+    }
+
+    pub fn high(&self) -> Amount {
+        match *self {
+            Magnitude::Exact(a) | Magnitude::Range(_, a) => a,
+        }
+    }
+
+    pub fn is_range(&self) -> bool {
+        match *self {
+            Magnitude::Exact(_) => false,
+            Magnitude::Range(..) => true,
+        }
+    }
+
+    /// Applies a function to each bound of this magnitude.
+    /// The function is expected to be monotonic, but may be decreasing (e.g. scaling by a negative factor).
+    pub fn map<F: Fn(Amount) -> Amount>(&self, f: F) -> Self {
+        match *self {
+            Magnitude::Exact(a) => Magnitude::Exact(f(a)),
+            Magnitude::Range(l, h) => Magnitude::range(f(l), f(h)),
+        }
+    }
+
+    /// Like `map`, but for a function that may fail, in which case so does this.
+    pub fn try_map<F: Fn(Amount) -> Option<Amount>>(&self, f: F) -> Option<Self> {
+        match *self {
+            Magnitude::Exact(a) => Some(Magnitude::Exact(f(a)?)),
+            Magnitude::Range(l, h) => Some(Magnitude::range(f(l)?, f(h)?)),
+        }
+    }
+
+    /// Adds two magnitudes, following interval arithmetic.
+    pub fn add(&self, other: &Self) -> Self {
+        match (*self, *other) {
+            (Magnitude::Exact(a), Magnitude::Exact(b)) => Magnitude::Exact(a + b),
+            _ => Magnitude::range(self.low() + other.low(), self.high() + other.high()),
+        }
+    }
+
+    /// Subtracts two magnitudes, following interval arithmetic.
+    pub fn sub(&self, other: &Self) -> Self {
+        match (*self, *other) {
+            (Magnitude::Exact(a), Magnitude::Exact(b)) => Magnitude::Exact(a - b),
+            _ => Magnitude::range(self.low() - other.high(), self.high() - other.low()),
+        }
+    }
+}
+
+impl fmt::Display for Magnitude {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Magnitude::Exact(ref a) => fmt_amount(a, f),
+            Magnitude::Range(ref l, ref h) => {
+                fmt_amount(l, f)?;
+                write!(f, "-")?;
+                fmt_amount(h, f)
+            },
+        }
+    }
+}
+
+/// An amount of something, measured in a given unit.
+/// Approximate quantities (e.g. "about 200 g") behave like exact ones, but remember that they are estimates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub struct Quantity {
+    magnitude: Magnitude,
+    unit: Unit,
+    approximate: bool,
+}
+
+impl Quantity {
+    pub fn new(amount: Amount, unit: Unit) -> Self {
+        Quantity::with_magnitude(Magnitude::Exact(amount), unit)
+    }
+
+    /// Creates a quantity of a whole number of units.
+    pub fn whole(amount: i64, unit: Unit) -> Self {
+        Quantity::new(Ratio::from_integer(amount), unit)
+    }
+
+    /// Creates a quantity spanning a closed range of amounts.
+    pub fn range(low: Amount, high: Amount, unit: Unit) -> Self {
+        Quantity::with_magnitude(Magnitude::range(low, high), unit)
+    }
+
+    pub fn with_magnitude(magnitude: Magnitude, unit: Unit) -> Self {
+        Quantity { magnitude, unit, approximate: false }
+    }
+
+    /// Marks this quantity as an estimate.
+    pub fn into_approximate(self) -> Self {
+        Quantity { approximate: true, ..self }
+    }
+
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    pub fn magnitude(&self) -> Magnitude {
+        self.magnitude
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.unit.dimension()
+    }
+
+    /// Applies a function to each bound of the magnitude of this quantity, optionally changing its unit.
+    pub fn map<F: Fn(Amount) -> Amount>(&self, f: F, unit: Unit) -> Self {
+        Quantity { magnitude: self.magnitude.map(f), unit, approximate: self.approximate }
+    }
+
+    /// Like `map`, but for a function that may fail, in which case so does this.
+    pub fn try_map<F: Fn(Amount) -> Option<Amount>>(&self, f: F, unit: Unit) -> Option<Self> {
+        Some(Quantity { magnitude: self.magnitude.try_map(f)?, unit, approximate: self.approximate })
+    }
+
+    /// Expresses another quantity in the unit of this quantity, failing if their dimensions differ.
+    fn align(&self, other: &Self) -> Result<Magnitude, Error> {
+        if self.dimension() != other.dimension() {
+            Err(QuantityError::DimensionMismatch{left: self.dimension(), right: other.dimension()})?;
+        }
+
+        let aligned = other.magnitude.try_map(|a| self.unit.unit_amount(other.unit.base_amount(a)?));
+        Ok(aligned.ok_or(ConversionError::Overflow)?)
+    }
+
+    /// Adds two quantities of the same dimension. The result is in the unit of the left-hand quantity,
+    /// and is approximate if either input is. Temperatures are points on a scale rather than amounts, so cannot be added.
+    pub fn try_add(&self, other: &Self) -> Result<Self, Error> {
+        if self.dimension() == Dimension::Temperature && other.dimension() == Dimension::Temperature {
+            Err(QuantityError::AbsoluteTemperatures{left: *self, right: *other})?;
+        }
+
+        let other_magnitude = self.align(other)?;
+        let magnitude = self.magnitude.add(&other_magnitude);
+        Ok(Quantity { magnitude, unit: self.unit, approximate: self.approximate || other.approximate })
+    }
+
+    /// Subtracts two quantities of the same dimension. The result is in the unit of the left-hand quantity,
+    /// and is approximate if either input is.
+    pub fn try_sub(&self, other: &Self) -> Result<Self, Error> {
+        let other_magnitude = self.align(other)?;
+        let magnitude = self.magnitude.sub(&other_magnitude);
+        Ok(Quantity { magnitude, unit: self.unit, approximate: self.approximate || other.approximate })
+    }
+
+    /// Totals a sequence of quantities of the same dimension, in the unit of the first quantity.
+    /// Produces `None` if there are no quantities.
+    pub fn sum<'a, II>(quantities: II) -> Result<Option<Self>, Error>
+    where II: IntoIterator<Item = &'a Quantity>
+    {
+        let mut total: Option<Quantity> = None;
+
+        for quantity in quantities {
+            total = Some(match total {
+                Some(t) => t.try_add(quantity)?,
+                None => *quantity,
+            });
+        }
+
+        Ok(total)
+    }
+
+    /// Compares the physical size of two quantities of the same dimension, regardless of their units.
+    /// Ranges can only be ordered if they do not overlap.
+    /// Note that this differs from `Ord`, which only orders quantities structurally.
+    pub fn compare(&self, other: &Self) -> Result<Ordering, Error> {
+        let other_magnitude = self.align(other)?;
+
+        match (self.magnitude, other_magnitude) {
+            (Magnitude::Exact(a), Magnitude::Exact(b)) => Ok(a.cmp(&b)),
+            (m, o) if m.high() < o.low() => Ok(Ordering::Less),
+            (m, o) if m.low() > o.high() => Ok(Ordering::Greater),
+            _ => Err(QuantityError::Overlapping{left: *self, right: *other})?,
+        }
+    }
+}
+
+/// Scales a quantity. Temperatures do not scale with a recipe, and are left unchanged.
+impl Mul<Amount> for Quantity {
+    type Output = Quantity;
+
+    fn mul(self, factor: Amount) -> Quantity {
+        match self.dimension() {
+            Dimension::Temperature => self,
+            _ => self.map(|a| a * factor, self.unit),
+        }
+    }
+}
+
+impl Div<Amount> for Quantity {
+    type Output = Quantity;
+
+    fn div(self, divisor: Amount) -> Quantity {
+        match self.dimension() {
+            Dimension::Temperature => self,
+            _ => self.map(|a| a / divisor, self.unit),
+        }
+    }
+}
+
+/// Writes an amount as a whole number, a proper fraction, or a mixed number.
+pub fn fmt_amount(amount: &Amount, f: &mut fmt::Formatter) -> fmt::Result {
+    let amount = match *amount.numer() < 0 {
+        true => { write!(f, "-")?; -amount }
+        false => *amount,
+    };
+
+    let whole = *amount.trunc().numer();
+    let fract = amount.fract();
+
+    match (whole == 0, fract.is_integer()) {
+        (_, true) => write!(f, "{}", whole),
+        (true, false) => write!(f, "{}/{}", fract.numer(), fract.denom()),
+        (false, false) => write!(f, "{} {}/{}", whole, fract.numer(), fract.denom()),
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.approximate {
+            write!(f, "about ")?;
+        }
+
+        write!(f, "{}", self.magnitude)?;
+
+        match self.unit {
+            Unit::Each => Ok(()),
+            _ => write!(f, " {}", self.unit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{Quantity, QuantityError, Magnitude};
+    use super::super::unit::{Dimension, Unit};
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_try_add() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Kilogram), Quantity::whole(500, Unit::Gram)),
+                Some(Quantity::new(Ratio::new(3, 2), Unit::Kilogram))),
+            ((Quantity::whole(500, Unit::Gram), Quantity::whole(1, Unit::Kilogram)),
+                Some(Quantity::whole(1500, Unit::Gram))),
+            ((Quantity::whole(1, Unit::Cup), Quantity::whole(4, Unit::Tablespoon)),
+                Some(Quantity::new(Ratio::new(5, 4), Unit::Cup))),
+            ((Quantity::whole(2, Unit::Each), Quantity::whole(3, Unit::Each)),
+                Some(Quantity::whole(5, Unit::Each))),
+            ((Quantity::whole(1, Unit::Hour), Quantity::whole(30, Unit::Minute)),
+                Some(Quantity::new(Ratio::new(3, 2), Unit::Hour))),
+            ((Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each), Quantity::whole(1, Unit::Each)),
+                Some(Quantity::range(Ratio::from_integer(3), Ratio::from_integer(4), Unit::Each))),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Kilogram), Quantity::range(Ratio::from_integer(500), Ratio::from_integer(1000), Unit::Gram)),
+                Some(Quantity::range(Ratio::new(3, 2), Ratio::from_integer(3), Unit::Kilogram))),
+            ((Quantity::whole(200, Unit::Gram).into_approximate(), Quantity::whole(50, Unit::Gram)),
+                Some(Quantity::whole(250, Unit::Gram).into_approximate())),
+            ((Quantity::whole(1, Unit::Cup), Quantity::whole(100, Unit::Gram)),
+                None),
+            ((Quantity::whole(180, Unit::Celsius), Quantity::whole(20, Unit::Celsius)),
+                None),
+        ];
+
+        for ((left, right), expected) in inputs_and_expected {
+            let produced = left.try_add(&right).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_try_sub() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Kilogram), Quantity::whole(250, Unit::Gram)),
+                Some(Quantity::new(Ratio::new(3, 4), Unit::Kilogram))),
+            ((Quantity::whole(1, Unit::Pound), Quantity::whole(4, Unit::Ounce)),
+                Some(Quantity::new(Ratio::new(3, 4), Unit::Pound))),
+            ((Quantity::range(Ratio::from_integer(4), Ratio::from_integer(5), Unit::Cup), Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Cup)),
+                Some(Quantity::range(Ratio::from_integer(2), Ratio::from_integer(4), Unit::Cup))),
+            ((Quantity::whole(1, Unit::Meter), Quantity::whole(1, Unit::Second)),
+                None),
+        ];
+
+        for ((left, right), expected) in inputs_and_expected {
+            let produced = left.try_sub(&right).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_sum() {
+        let inputs_and_expected = vec![
+            (vec![], Some(None)),
+            (vec![Quantity::whole(2, Unit::Each)], Some(Some(Quantity::whole(2, Unit::Each)))),
+            (
+                vec![
+                    Quantity::whole(1, Unit::Cup),
+                    Quantity::range(Ratio::from_integer(2), Ratio::from_integer(4), Unit::Tablespoon),
+                    Quantity::whole(4, Unit::Tablespoon).into_approximate(),
+                ],
+                Some(Some(Quantity::range(Ratio::new(11, 8), Ratio::new(3, 2), Unit::Cup).into_approximate())),
+            ),
+            (vec![Quantity::whole(1, Unit::Cup), Quantity::whole(1, Unit::Gram)], None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Quantity::sum(&input).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(1, Unit::Kilogram), Quantity::whole(999, Unit::Gram)), Some(Ordering::Greater)),
+            ((Quantity::whole(1, Unit::Kilogram), Quantity::whole(1000, Unit::Gram)), Some(Ordering::Equal)),
+            ((Quantity::whole(1, Unit::Tablespoon), Quantity::whole(3, Unit::Teaspoon)), Some(Ordering::Equal)),
+            ((Quantity::whole(1, Unit::Ounce), Quantity::whole(30, Unit::Gram)), Some(Ordering::Less)),
+            ((Quantity::whole(0, Unit::Celsius), Quantity::whole(32, Unit::Fahrenheit)), Some(Ordering::Equal)),
+            ((Quantity::whole(100, Unit::Celsius), Quantity::whole(200, Unit::Fahrenheit)), Some(Ordering::Greater)),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Cup), Quantity::whole(3, Unit::Cup)), Some(Ordering::Less)),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Kilogram), Quantity::whole(900, Unit::Gram)), Some(Ordering::Greater)),
+            ((Quantity::range(Ratio::from_integer(1), Ratio::from_integer(2), Unit::Cup), Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Cup)), None),
+            ((Quantity::whole(1, Unit::Inch), Quantity::whole(1, Unit::Each)), None),
+        ];
+
+        for ((left, right), expected) in inputs_and_expected {
+            let produced = left.compare(&right).ok();
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let produced = Quantity::whole(1, Unit::Cup).try_add(&Quantity::whole(1, Unit::Gram))
+            .unwrap_err()
+            .downcast::<QuantityError>()
+            .expect("Unexpected error type");
+        let expected = QuantityError::DimensionMismatch{left: Dimension::Volume, right: Dimension::Mass};
+
+        assert_eq!(expected, produced);
+    }
+
+    #[test]
+    fn test_absolute_temperatures() {
+        let left = Quantity::whole(180, Unit::Celsius);
+        let right = Quantity::whole(350, Unit::Fahrenheit);
+        let produced = Quantity::sum(&[left, right])
+            .unwrap_err()
+            .downcast::<QuantityError>()
+            .expect("Unexpected error type");
+        let expected = QuantityError::AbsoluteTemperatures{left, right};
+
+        assert_eq!(expected, produced);
+        assert_eq!(Some(left), Quantity::sum(&[left]).expect("Unable to sum"));
+    }
+
+    #[test]
+    fn test_magnitude_range() {
+        let inputs_and_expected = vec![
+            ((Ratio::from_integer(2), Ratio::from_integer(3)), Magnitude::Range(Ratio::from_integer(2), Ratio::from_integer(3))),
+            ((Ratio::from_integer(3), Ratio::from_integer(2)), Magnitude::Range(Ratio::from_integer(2), Ratio::from_integer(3))),
+            ((Ratio::new(1, 2), Ratio::new(2, 4)), Magnitude::Exact(Ratio::new(1, 2))),
+        ];
+
+        for ((a, b), expected) in inputs_and_expected {
+            let produced = Magnitude::range(a, b);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_scale() {
+        let inputs_and_expected = vec![
+            ((Quantity::whole(2, Unit::Cup), Ratio::new(3, 4)), Quantity::new(Ratio::new(3, 2), Unit::Cup)),
+            ((Quantity::whole(3, Unit::Each), Ratio::from_integer(2)), Quantity::whole(6, Unit::Each)),
+            ((Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each), Ratio::from_integer(2)),
+                Quantity::range(Ratio::from_integer(4), Ratio::from_integer(6), Unit::Each)),
+            ((Quantity::whole(200, Unit::Gram).into_approximate(), Ratio::from_integer(2)),
+                Quantity::whole(400, Unit::Gram).into_approximate()),
+            ((Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each), Ratio::from_integer(-1)),
+                Quantity::range(Ratio::from_integer(-3), Ratio::from_integer(-2), Unit::Each)),
+            ((Quantity::whole(180, Unit::Celsius), Ratio::from_integer(2)), Quantity::whole(180, Unit::Celsius)),
+            ((Quantity::whole(350, Unit::Fahrenheit), Ratio::new(1, 2)), Quantity::whole(350, Unit::Fahrenheit)),
+        ];
+
+        for ((quantity, factor), expected) in inputs_and_expected {
+            let produced = quantity * factor;
+            assert_eq!(expected, produced);
+            assert_eq!(quantity, produced / factor);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let inputs_and_expected = vec![
+            (Quantity::whole(200, Unit::Gram), "200 g"),
+            (Quantity::new(Ratio::new(1, 2), Unit::Cup), "1/2 cup"),
+            (Quantity::new(Ratio::new(3, 2), Unit::Tablespoon), "1 1/2 tbsp"),
+            (Quantity::new(Ratio::new(2, 1), Unit::FluidOunce), "2 fl oz"),
+            (Quantity::whole(3, Unit::Each), "3"),
+            (Quantity::whole(-18, Unit::Celsius), "-18 °C"),
+            (Quantity::new(Ratio::new(-5, 4), Unit::Fahrenheit), "-1 1/4 °F"),
+            (Quantity::range(Ratio::from_integer(2), Ratio::from_integer(3), Unit::Each), "2-3"),
+            (Quantity::range(Ratio::new(3, 2), Ratio::from_integer(2), Unit::Cup), "1 1/2-2 cup"),
+            (Quantity::whole(200, Unit::Gram).into_approximate(), "about 200 g"),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = format!("{}", input);
+            assert_eq!(expected, produced);
+        }
+    }
+}
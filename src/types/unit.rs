@@ -0,0 +1,471 @@
+use std::fmt;
+use std::str::FromStr;
+
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub};
+
+/// An exact rational amount, used for all magnitudes to avoid floating point drift when scaling.
+pub type Amount = Ratio<i64>;
+
+/// Parses an amount written as an integer (`2`), a decimal (`0.75`), or a fraction (`3/4`).
+pub fn parse_amount(repr: &str) -> Option<Amount> {
+    let repr = repr.trim();
+
+    if let Some(pos) = repr.find('/') {
+        let numer: i64 = repr[..pos].parse().ok()?;
+        let denom: i64 = repr[pos + 1..].parse().ok()?;
+
+        return match denom {
+            0 => None,
+            _ => Some(Ratio::new(numer, denom)),
+        };
+    }
+
+    match repr.find('.') {
+        Some(pos) => {
+            let (whole, fract) = (&repr[..pos], &repr[pos + 1..]);
+
+            if whole.is_empty() || fract.is_empty() || !fract.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+
+            let denom = 10i64.checked_pow(fract.len() as u32)?;
+            let numer: i64 = format!("{}{}", whole, fract).parse().ok()?;
+
+            Some(Ratio::new(numer, denom))
+        },
+        None => repr.parse().ok().map(Ratio::from_integer),
+    }
+}
+
+/// Looks up the value of a single-character Unicode vulgar fraction, such as `½`.
+pub fn vulgar_fraction_value(c: char) -> Option<Amount> {
+    let (numer, denom) = match c {
+        '½' => (1, 2),
+        '⅓' => (1, 3),
+        '⅔' => (2, 3),
+        '¼' => (1, 4),
+        '¾' => (3, 4),
+        '⅕' => (1, 5),
+        '⅖' => (2, 5),
+        '⅗' => (3, 5),
+        '⅘' => (4, 5),
+        '⅙' => (1, 6),
+        '⅚' => (5, 6),
+        '⅐' => (1, 7),
+        '⅛' => (1, 8),
+        '⅜' => (3, 8),
+        '⅝' => (5, 8),
+        '⅞' => (7, 8),
+        '⅑' => (1, 9),
+        '⅒' => (1, 10),
+        _ => return None,
+    };
+
+    Some(Ratio::new(numer, denom))
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum UnitError {
+    #[fail(display = "unknown unit: {}", repr)]
+    Unknown {
+        repr: String,
+    },
+}
+
+/// Represents the physical dimension that a unit measures.
+/// Quantities can only be compared or combined if they share a dimension.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum Dimension {
+    Mass,
+    Volume,
+    Count,
+    Length,
+    Temperature,
+    Time,
+}
+
+impl Dimension {
+    /// The unit that all other units of this dimension are defined in terms of.
+    pub fn base_unit(&self) -> Unit {
+        match *self {
+            Dimension::Mass => Unit::Gram,
+            Dimension::Volume => Unit::Milliliter,
+            Dimension::Count => Unit::Each,
+            Dimension::Length => Unit::Millimeter,
+            Dimension::Temperature => Unit::Kelvin,
+            Dimension::Time => Unit::Second,
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Dimension::Mass => "mass",
+            Dimension::Volume => "volume",
+            Dimension::Count => "count",
+            Dimension::Length => "length",
+            Dimension::Temperature => "temperature",
+            Dimension::Time => "time",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A unit of measure. Volume units without a qualifier are US customary.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+pub enum Unit {
+    Milligram,
+    Gram,
+    Kilogram,
+    Ounce,
+    Pound,
+
+    Milliliter,
+    Liter,
+    Teaspoon,
+    Tablespoon,
+    Cup,
+    FluidOunce,
+    Pint,
+    Quart,
+    Gallon,
+    ImperialTeaspoon,
+    ImperialTablespoon,
+    ImperialFluidOunce,
+    ImperialCup,
+    ImperialPint,
+    ImperialQuart,
+    ImperialGallon,
+
+    Each,
+
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+
+    Second,
+    Minute,
+    Hour,
+}
+
+impl Unit {
+    pub fn dimension(&self) -> Dimension {
+        match *self {
+            Unit::Milligram | Unit::Gram | Unit::Kilogram | Unit::Ounce | Unit::Pound => Dimension::Mass,
+            Unit::Milliliter | Unit::Liter | Unit::Teaspoon | Unit::Tablespoon | Unit::Cup
+            | Unit::FluidOunce | Unit::Pint | Unit::Quart | Unit::Gallon
+            | Unit::ImperialTeaspoon | Unit::ImperialTablespoon | Unit::ImperialFluidOunce | Unit::ImperialCup
+            | Unit::ImperialPint | Unit::ImperialQuart | Unit::ImperialGallon => Dimension::Volume,
+            Unit::Each => Dimension::Count,
+            Unit::Millimeter | Unit::Centimeter | Unit::Meter | Unit::Inch => Dimension::Length,
+            Unit::Celsius | Unit::Fahrenheit | Unit::Kelvin => Dimension::Temperature,
+            Unit::Second | Unit::Minute | Unit::Hour => Dimension::Time,
+        }
+    }
+
+    /// The number of base units in one of this unit.
+    pub fn factor(&self) -> Amount {
+        match *self {
+            Unit::Milligram => Ratio::new(1, 1000),
+            Unit::Gram => Ratio::from_integer(1),
+            Unit::Kilogram => Ratio::from_integer(1000),
+            // Defined exactly as 453.59237 g per pound.
+            Unit::Ounce => Ratio::new(45_359_237, 1_600_000),
+            Unit::Pound => Ratio::new(45_359_237, 100_000),
+
+            Unit::Milliliter => Ratio::from_integer(1),
+            Unit::Liter => Ratio::from_integer(1000),
+            // Defined exactly as 231 cubic inches per gallon.
+            Unit::Teaspoon => Ratio::new(157_725_491, 32_000_000),
+            Unit::Tablespoon => Ratio::new(473_176_473, 32_000_000),
+            Unit::Cup => Ratio::new(473_176_473, 2_000_000),
+            Unit::FluidOunce => Ratio::new(473_176_473, 16_000_000),
+            Unit::Pint => Ratio::new(473_176_473, 1_000_000),
+            Unit::Quart => Ratio::new(473_176_473, 500_000),
+            Unit::Gallon => Ratio::new(473_176_473, 125_000),
+            // Defined exactly as 4.54609 l per imperial gallon.
+            Unit::ImperialTeaspoon => Ratio::new(454_609, 76_800),
+            Unit::ImperialTablespoon => Ratio::new(454_609, 25_600),
+            Unit::ImperialFluidOunce => Ratio::new(454_609, 16_000),
+            Unit::ImperialCup => Ratio::new(454_609, 1_600),
+            Unit::ImperialPint => Ratio::new(454_609, 800),
+            Unit::ImperialQuart => Ratio::new(454_609, 400),
+            Unit::ImperialGallon => Ratio::new(454_609, 100),
+
+            Unit::Each => Ratio::from_integer(1),
+
+            Unit::Millimeter => Ratio::from_integer(1),
+            Unit::Centimeter => Ratio::from_integer(10),
+            Unit::Meter => Ratio::from_integer(1000),
+            Unit::Inch => Ratio::new(127, 5),
+
+            Unit::Celsius | Unit::Kelvin => Ratio::from_integer(1),
+            Unit::Fahrenheit => Ratio::new(5, 9),
+
+            Unit::Second => Ratio::from_integer(1),
+            Unit::Minute => Ratio::from_integer(60),
+            Unit::Hour => Ratio::from_integer(3600),
+        }
+    }
+
+    /// The number of base units that the zero point of this unit lies at.
+    /// This is only nonzero for the temperature scales not anchored at absolute zero.
+    pub fn offset(&self) -> Amount {
+        match *self {
+            Unit::Celsius => Ratio::new(27_315, 100),
+            Unit::Fahrenheit => Ratio::new(45_967, 180),
+            _ => Ratio::from_integer(0),
+        }
+    }
+
+    /// Converts an amount in this unit to an amount in the base unit of its dimension.
+    /// Returns `None` if the amount is too large or too precise to be converted exactly.
+    pub fn base_amount(self, amount: Amount) -> Option<Amount> {
+        amount.checked_mul(&self.factor())?.checked_add(&self.offset())
+    }
+
+    /// Converts an amount in the base unit of this unit's dimension to an amount in this unit.
+    /// Returns `None` if the amount is too large or too precise to be converted exactly.
+    pub fn unit_amount(self, base_amount: Amount) -> Option<Amount> {
+        base_amount.checked_sub(&self.offset())?.checked_div(&self.factor())
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Unit::Milligram => "mg",
+            Unit::Gram => "g",
+            Unit::Kilogram => "kg",
+            Unit::Ounce => "oz",
+            Unit::Pound => "lb",
+            Unit::Milliliter => "ml",
+            Unit::Liter => "l",
+            Unit::Teaspoon => "tsp",
+            Unit::Tablespoon => "tbsp",
+            Unit::Cup => "cup",
+            Unit::FluidOunce => "fl oz",
+            Unit::Pint => "pt",
+            Unit::Quart => "qt",
+            Unit::Gallon => "gal",
+            Unit::ImperialTeaspoon => "imp tsp",
+            Unit::ImperialTablespoon => "imp tbsp",
+            Unit::ImperialFluidOunce => "imp fl oz",
+            Unit::ImperialCup => "imp cup",
+            Unit::ImperialPint => "imp pt",
+            Unit::ImperialQuart => "imp qt",
+            Unit::ImperialGallon => "imp gal",
+            Unit::Each => "",
+            Unit::Millimeter => "mm",
+            Unit::Centimeter => "cm",
+            Unit::Meter => "m",
+            Unit::Inch => "in",
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Kelvin => "K",
+            Unit::Second => "s",
+            Unit::Minute => "min",
+            Unit::Hour => "h",
+        }
+    }
+}
+
+impl FromStr for Unit {
+    type Err = UnitError;
+
+    /// Parses a unit from its symbol or its singular or plural name, ignoring case.
+    /// Temperature scales are not given by a bare letter, as "2 c" is more likely to mean cups than degrees Celsius;
+    /// the one exception is the kelvin symbol, which must be an uppercase "K".
+    fn from_str(repr: &str) -> Result<Self, Self::Err> {
+        if repr.trim() == Unit::Kelvin.symbol() {
+            return Ok(Unit::Kelvin);
+        }
+
+        let normalized = repr.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+
+        let unit = match normalized.as_str() {
+            "mg" | "milligram" | "milligrams" => Unit::Milligram,
+            "g" | "gram" | "grams" => Unit::Gram,
+            "kg" | "kilogram" | "kilograms" => Unit::Kilogram,
+            "oz" | "ounce" | "ounces" => Unit::Ounce,
+            "lb" | "lbs" | "pound" | "pounds" => Unit::Pound,
+
+            "ml" | "milliliter" | "milliliters" | "millilitre" | "millilitres" => Unit::Milliliter,
+            "l" | "liter" | "liters" | "litre" | "litres" => Unit::Liter,
+            "tsp" | "teaspoon" | "teaspoons" => Unit::Teaspoon,
+            "tbsp" | "tablespoon" | "tablespoons" => Unit::Tablespoon,
+            "cup" | "cups" => Unit::Cup,
+            "fl oz" | "fluid ounce" | "fluid ounces" => Unit::FluidOunce,
+            "pt" | "pint" | "pints" => Unit::Pint,
+            "qt" | "quart" | "quarts" => Unit::Quart,
+            "gal" | "gallon" | "gallons" => Unit::Gallon,
+            "imp tsp" | "imperial teaspoon" | "imperial teaspoons" => Unit::ImperialTeaspoon,
+            "imp tbsp" | "imperial tablespoon" | "imperial tablespoons" => Unit::ImperialTablespoon,
+            "imp fl oz" | "imperial fluid ounce" | "imperial fluid ounces" => Unit::ImperialFluidOunce,
+            "imp cup" | "imperial cup" | "imperial cups" => Unit::ImperialCup,
+            "imp pt" | "imperial pint" | "imperial pints" => Unit::ImperialPint,
+            "imp qt" | "imperial quart" | "imperial quarts" => Unit::ImperialQuart,
+            "imp gal" | "imperial gallon" | "imperial gallons" => Unit::ImperialGallon,
+
+            "each" => Unit::Each,
+
+            "mm" | "millimeter" | "millimeters" | "millimetre" | "millimetres" => Unit::Millimeter,
+            "cm" | "centimeter" | "centimeters" | "centimetre" | "centimetres" => Unit::Centimeter,
+            "m" | "meter" | "meters" | "metre" | "metres" => Unit::Meter,
+            "in" | "inch" | "inches" => Unit::Inch,
+
+            "°c" | "celsius" => Unit::Celsius,
+            "°f" | "fahrenheit" => Unit::Fahrenheit,
+            "kelvin" | "kelvins" => Unit::Kelvin,
+
+            "s" | "sec" | "second" | "seconds" => Unit::Second,
+            "min" | "minute" | "minutes" => Unit::Minute,
+            "h" | "hr" | "hour" | "hours" => Unit::Hour,
+
+            _ => return Err(UnitError::Unknown{repr: repr.to_string()}),
+        };
+
+        Ok(unit)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Unit, Dimension, parse_amount, vulgar_fraction_value};
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_base_amount() {
+        let inputs_and_expected = vec![
+            ((Unit::Kilogram, Ratio::new(3, 2)), Ratio::from_integer(1500)),
+            ((Unit::Pound, Ratio::from_integer(1)), Ratio::new(45_359_237, 100_000)),
+            ((Unit::Ounce, Ratio::from_integer(16)), Ratio::new(45_359_237, 100_000)),
+            ((Unit::Tablespoon, Ratio::from_integer(1)), Unit::Teaspoon.base_amount(Ratio::from_integer(3)).unwrap()),
+            ((Unit::Cup, Ratio::from_integer(1)), Unit::Tablespoon.base_amount(Ratio::from_integer(16)).unwrap()),
+            ((Unit::Gallon, Ratio::from_integer(1)), Unit::Cup.base_amount(Ratio::from_integer(16)).unwrap()),
+            ((Unit::ImperialGallon, Ratio::from_integer(1)), Ratio::new(454_609, 100)),
+            ((Unit::ImperialPint, Ratio::from_integer(1)), Unit::ImperialFluidOunce.base_amount(Ratio::from_integer(20)).unwrap()),
+            ((Unit::ImperialTablespoon, Ratio::from_integer(1)), Unit::ImperialTeaspoon.base_amount(Ratio::from_integer(3)).unwrap()),
+            ((Unit::Celsius, Ratio::from_integer(0)), Ratio::new(27_315, 100)),
+            ((Unit::Fahrenheit, Ratio::from_integer(32)), Ratio::new(27_315, 100)),
+            ((Unit::Fahrenheit, Ratio::from_integer(212)), Ratio::new(37_315, 100)),
+            ((Unit::Hour, Ratio::from_integer(2)), Ratio::from_integer(7200)),
+        ];
+
+        for ((unit, amount), expected) in inputs_and_expected {
+            let produced = unit.base_amount(amount).expect("Unable to convert");
+            assert_eq!(expected, produced);
+
+            // Converting back should produce the original amount.
+            assert_eq!(Some(amount), unit.unit_amount(produced));
+        }
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        let inputs_and_expected = vec![
+            ("2", Some(Ratio::from_integer(2))),
+            (" 12 ", Some(Ratio::from_integer(12))),
+            ("0.75", Some(Ratio::new(3, 4))),
+            ("1.5", Some(Ratio::new(3, 2))),
+            ("010.010", Some(Ratio::new(1001, 100))),
+            ("3/4", Some(Ratio::new(3, 4))),
+            ("6/4", Some(Ratio::new(3, 2))),
+            ("1/0", None),
+            (".5", None),
+            ("5.", None),
+            ("1.-5", None),
+            ("half", None),
+            ("", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = parse_amount(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_vulgar_fraction_value() {
+        let inputs_and_expected = vec![
+            ('½', Some(Ratio::new(1, 2))),
+            ('⅔', Some(Ratio::new(2, 3))),
+            ('⅞', Some(Ratio::new(7, 8))),
+            ('2', None),
+            ('/', None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = vulgar_fraction_value(input);
+            assert_eq!(expected, produced);
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let inputs_and_expected = vec![
+            ("g", Some(Unit::Gram)),
+            ("Grams", Some(Unit::Gram)),
+            ("cups", Some(Unit::Cup)),
+            ("fl oz", Some(Unit::FluidOunce)),
+            ("fl   oz", Some(Unit::FluidOunce)),
+            ("imp fl oz", Some(Unit::ImperialFluidOunce)),
+            ("Imperial Pints", Some(Unit::ImperialPint)),
+            ("°C", Some(Unit::Celsius)),
+            ("°f", Some(Unit::Fahrenheit)),
+            ("Kelvin", Some(Unit::Kelvin)),
+            ("K", Some(Unit::Kelvin)),
+            // Bare letters are too easily mistaken for other units, e.g. "c" for cups.
+            ("c", None),
+            ("C", None),
+            ("F", None),
+            ("k", None),
+            ("min", Some(Unit::Minute)),
+            ("cloves", None),
+            ("", None),
+        ];
+
+        for (input, expected) in inputs_and_expected {
+            let produced = Unit::from_str(input).ok();
+            assert_eq!(expected, produced);
+        }
+
+        // Every unit should be parseable from its own symbol.
+        for &unit in &[Unit::Milligram, Unit::Pound, Unit::Gallon, Unit::ImperialTablespoon, Unit::Kelvin, Unit::Hour] {
+            assert_eq!(Ok(unit), Unit::from_str(unit.symbol()));
+        }
+    }
+
+    #[test]
+    fn test_dimension() {
+        let inputs_and_expected = vec![
+            (Unit::Gram, Dimension::Mass),
+            (Unit::FluidOunce, Dimension::Volume),
+            (Unit::Each, Dimension::Count),
+            (Unit::Inch, Dimension::Length),
+            (Unit::Fahrenheit, Dimension::Temperature),
+            (Unit::Minute, Dimension::Time),
+        ];
+
+        for (unit, expected) in inputs_and_expected {
+            let produced = unit.dimension();
+            assert_eq!(expected, produced);
+            assert_eq!(expected, produced.base_unit().dimension());
+        }
+    }
+}